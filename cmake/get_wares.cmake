@@ -0,0 +1,41 @@
+# small script to download the wares package manager module and include it into a cmake
+# project
+message(FATAL_ERROR "Download this file directly from the releases on github!")
+
+set(WARES_VERSION {{WARES_VERSION}})
+# sha256 hash of the wares file
+set(WARES_MODULE_HASH {{WARES_HASH}})
+
+# download settings
+set(WARES_RETRY_COUNT 5)
+
+# choose an install directory
+set(WARES_INSTALL_FILE "${CMAKE_CURRENT_LIST_DIR}/wares/wares.cmake")
+
+# the module is downloaded again when it's missing or isn't the one for this version
+set(WARES_INSTALLED_HASH "")
+if(EXISTS "${WARES_INSTALL_FILE}")
+    file(SHA256 "${WARES_INSTALL_FILE}" WARES_INSTALLED_HASH)
+endif()
+
+if(NOT WARES_INSTALLED_HASH STREQUAL WARES_MODULE_HASH)
+    foreach(WARES_ATTEMPT RANGE 1 ${WARES_RETRY_COUNT})
+        file(DOWNLOAD "https://github.com/lochnessdragon/wares/releases/download/v${WARES_VERSION}/wares.cmake" "${WARES_INSTALL_FILE}"
+            EXPECTED_HASH SHA256=${WARES_MODULE_HASH}
+            STATUS WARES_DOWNLOAD_STATUS)
+
+        list(GET WARES_DOWNLOAD_STATUS 0 WARES_DOWNLOAD_CODE)
+        if(WARES_DOWNLOAD_CODE EQUAL 0)
+            break()
+        endif()
+
+        list(GET WARES_DOWNLOAD_STATUS 1 WARES_DOWNLOAD_ERROR)
+        message(WARNING "[wares]: failed to download wares.cmake: ${WARES_DOWNLOAD_ERROR}, retrying... (${WARES_ATTEMPT})")
+    endforeach()
+
+    if(NOT WARES_DOWNLOAD_CODE EQUAL 0)
+        message(FATAL_ERROR "[wares]: failed to download wares.cmake ${WARES_VERSION}")
+    endif()
+endif()
+
+include("${WARES_INSTALL_FILE}")
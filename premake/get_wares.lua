@@ -3,7 +3,7 @@
 error("Download this file directly from the releases on github!")
 
 local version = {{WARES_VERSION}}
--- md5 hash of the wares file
+-- sha256 hash of the wares file
 local module_hash = {{WARES_HASH}}
 
 -- check to see if wares has already been installed somewhere in the premake path
@@ -20,19 +20,19 @@ local install_file = path.getabsolute("./wares/wares.lua")
 
 -- generate the command needed for hashing
 -- on windows, this is of the form:
--- certutil -hashfile "wares\wares.lua" MD5
+-- certutil -hashfile "wares\wares.lua" SHA256
 -- on linux:
--- md5sum "wares\wares.lua"
+-- sha256sum "wares\wares.lua"
 -- on macos:
--- openssl md5 "wares\wares.lua"
+-- shasum -a 256 "wares\wares.lua"
 os_id = os.host()
 local hash_cmd = ""
 if os_id == "windows" then
-	hash_cmd = string.format("certutil -hashfile \"%s\" MD5", install_file)
+	hash_cmd = string.format("certutil -hashfile \"%s\" SHA256", install_file)
 elseif os_id == "linux" then
-	hash_cmd = string.format("md5sum \"%s\"", install_file)
+	hash_cmd = string.format("sha256sum \"%s\"", install_file)
 elseif os_id == "macosx" then
-	hash_cmd = string.format("cat \"%s\" | md5", install_file)
+	hash_cmd = string.format("shasum -a 256 \"%s\"", install_file)
 end
 
 function progress(total, current)
@@ -77,10 +77,10 @@ for i = 1, retry_count do
 		if os_id == "windows" then
 			-- extract hash
 			-- looks something like:
-			-- MD5 hash of <path>:
-			-- 2e9d229043106b624b9da4c63be01207
+			-- SHA256 hash of <path>:
+			-- 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
 			-- CertUtil: -hashfile command completed successfully.
-			local match_start, match_end, potential_hash = string.find(hash_output, "MD5 hash of " .. install_file .. ":\n(%w+)")
+			local match_start, match_end, potential_hash = string.find(hash_output, "SHA256 hash of " .. install_file .. ":\n(%w+)")
 			if potential_hash ~= nil then 
 				hash = potential_hash
 			else
@@ -93,7 +93,7 @@ for i = 1, retry_count do
 		elseif os_id == "linux" then
 			-- extract hash
 			-- looks something like:
-			-- 2e9d229043106b624b9da4c63be01207  <path>
+			-- 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  <path>
 			local match_start, match_end, potential_hash = string.find(hash_output, "(%w+)")
 			if potential_hash ~= nil then 
 				hash = potential_hash
@@ -107,7 +107,7 @@ for i = 1, retry_count do
 		elseif os_id == "macosx" then
 			-- extract hash
 			-- looks something like:
-			-- 2e9d229043106b624b9da4c63be01207
+			-- 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  <path>
 			local match_start, match_end, potential_hash = string.find(hash_output, "(%w+)")
 			if potential_hash ~= nil then 
 				hash = potential_hash
@@ -0,0 +1,370 @@
+// archive packages (release tarballs and zip files)
+//
+// archives are identified by the sha256 of the downloaded file. downloads are kept in a shared folder
+// under the system's temporary directory, named by that hash, so an archive that was downloaded to lock
+// it doesn't have to be downloaded again to install it.
+
+// standard libraries
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// hashing
+use sha2::{Sha256, Digest};
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::utils;
+use crate::{SyncError, IoSnafu, DownloadSnafu, ArchiveHashMismatchSnafu};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+	TarGz,
+	TarXz,
+	TarBz2,
+	Zip
+}
+
+impl ArchiveFormat {
+	// guesses the format from the file name at the end of a url
+	pub fn from_url(url: &str) -> Option<ArchiveFormat> {
+		let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+
+		if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+			Some(ArchiveFormat::TarGz)
+		} else if path.ends_with(".tar.xz") || path.ends_with(".txz") {
+			Some(ArchiveFormat::TarXz)
+		} else if path.ends_with(".tar.bz2") || path.ends_with(".tbz2") {
+			Some(ArchiveFormat::TarBz2)
+		} else if path.ends_with(".zip") {
+			Some(ArchiveFormat::Zip)
+		} else {
+			None
+		}
+	}
+
+	pub fn extension(&self) -> &'static str {
+		match self {
+			ArchiveFormat::TarGz => "tar.gz",
+			ArchiveFormat::TarXz => "tar.xz",
+			ArchiveFormat::TarBz2 => "tar.bz2",
+			ArchiveFormat::Zip => "zip"
+		}
+	}
+}
+
+// the file name at the end of an archive url (i.e. zlib-1.3.1.tar.gz)
+pub fn archive_name(url: &str) -> String {
+	let path = url.split(['?', '#']).next().unwrap_or(url);
+	utils::sanitize_filename(path.rsplit('/').next().unwrap_or(path))
+}
+
+// a short hash of an archive's full url, so archives with the same file name from different places are told apart
+pub fn url_hash(url: &str) -> String {
+	let digest: [u8; 32] = Sha256::digest(url.as_bytes()).into();
+	let mut short = utils::format_hex(&digest);
+	short.truncate(12);
+	short
+}
+
+fn downloads_folder() -> PathBuf {
+	std::env::temp_dir().join("wares-downloads")
+}
+
+fn download_path(sha256: &str, format: ArchiveFormat) -> PathBuf {
+	downloads_folder().join(format!("{sha256}.{}", format.extension()))
+}
+
+// the sha256 of a file as lowercase hex
+pub fn sha256_of(path: &Path) -> Result<String, SyncError> {
+	let mut reader = BufReader::new(File::open(path).context(IoSnafu{ context: format!("opening {:?}", path) })?);
+	let mut hasher = Sha256::new();
+	io::copy(&mut reader, &mut hasher).context(IoSnafu{ context: format!("reading {:?}", path) })?;
+
+	let digest: [u8; 32] = hasher.finalize().into();
+	Ok(utils::format_hex(&digest))
+}
+
+// downloads an archive into the downloads folder and returns its sha256
+pub fn download(url: &str) -> Result<String, SyncError> {
+	let format = ArchiveFormat::from_url(url).ok_or_else(|| SyncError::Extract{ archive: url.to_string(), message: String::from("unknown archive format") })?;
+
+	let folder = downloads_folder();
+	fs::create_dir_all(&folder).context(IoSnafu{ context: format!("creating {:?}", folder) })?;
+
+	// download next to the final file and move it into place once the hash is known
+	// (every call gets its own file, other jobs can be downloading an archive with the same name at the same time)
+	static DOWNLOADS: AtomicUsize = AtomicUsize::new(0);
+	let partial = folder.join(format!("{}-{}-{}.part", archive_name(url), std::process::id(), DOWNLOADS.fetch_add(1, Ordering::Relaxed)));
+	let response = ureq::get(url).call().map_err(Box::new).context(DownloadSnafu{ url })?;
+
+	let mut hasher = Sha256::new();
+	{
+		let mut reader = response.into_reader();
+		let mut writer = File::create(&partial).context(IoSnafu{ context: format!("creating {:?}", partial) })?;
+		let mut buffer = [0u8; 64 * 1024];
+		loop {
+			let read = reader.read(&mut buffer).context(IoSnafu{ context: format!("downloading {url}") })?;
+			if read == 0 {
+				break;
+			}
+			hasher.update(&buffer[..read]);
+			writer.write_all(&buffer[..read]).context(IoSnafu{ context: format!("writing {:?}", partial) })?;
+		}
+	}
+
+	let digest: [u8; 32] = hasher.finalize().into();
+	let sha256 = utils::format_hex(&digest);
+	let destination = download_path(&sha256, format);
+	fs::rename(&partial, &destination).context(IoSnafu{ context: format!("moving {:?} to {:?}", partial, destination) })?;
+
+	Ok(sha256)
+}
+
+// returns the path of a downloaded archive with the expected sha256, downloading it if needed
+pub fn fetch(url: &str, sha256: &str) -> Result<PathBuf, SyncError> {
+	let format = ArchiveFormat::from_url(url).ok_or_else(|| SyncError::Extract{ archive: url.to_string(), message: String::from("unknown archive format") })?;
+	let path = download_path(sha256, format);
+
+	// a download left over from locking the archive can be reused, as long as it wasn't changed since
+	if path.exists() && sha256_of(&path)? == sha256 {
+		return Ok(path);
+	}
+
+	let found = download(url)?;
+	if found != sha256 {
+		let _ = fs::remove_file(download_path(&found, format));
+		return ArchiveHashMismatchSnafu{ url, expected: sha256, found }.fail();
+	}
+
+	Ok(path)
+}
+
+// the path of an archive entry with its first components removed, or None if nothing is left of it
+// entries that try to escape the destination folder are rejected
+fn strip_path(path: &Path, strip_components: usize) -> Result<Option<PathBuf>, String> {
+	let mut stripped = PathBuf::new();
+	for component in path.components().skip(strip_components) {
+		match component {
+			Component::Normal(part) => stripped.push(part),
+			Component::CurDir => {},
+			_ => return Err(format!("the entry {} points outside of the archive", path.display()))
+		}
+	}
+
+	Ok(if stripped.as_os_str().is_empty() { None } else { Some(stripped) })
+}
+
+// where path leads from base (a real folder inside of root), following the links already on disk at every step
+// None if it leaves root along the way. folders that don't exist yet could still be turned into links by a later
+// entry, so there's no going back up (..) through them
+fn resolve_inside(root: &Path, base: &Path, path: &Path) -> Option<PathBuf> {
+	let mut resolved = base.to_path_buf();
+	let mut exists = true;
+	for component in path.components() {
+		match component {
+			Component::Normal(part) => {
+				resolved.push(part);
+				if exists {
+					match resolved.canonicalize() {
+						Ok(real) => resolved = real,
+						Err(_) => exists = false
+					}
+				}
+			},
+			Component::CurDir => {},
+			Component::ParentDir if exists => {
+				resolved.pop();
+			},
+			_ => return None
+		}
+
+		if !resolved.starts_with(root) {
+			return None;
+		}
+	}
+
+	Some(resolved)
+}
+
+// true if an entry (after stripping) is one of the paths, or inside of one
+fn is_selected(entry: &Path, paths: Option<&[String]>) -> bool {
+	let Some(paths) = paths else {
+		return true;
+	};
+
+	paths.iter().any(|path| entry.starts_with(path))
+}
+
+// extracts an archive into an empty folder, dropping the first strip_components folders of every entry
+// only the entries inside of paths are extracted, if there are any
+pub fn extract(archive: &Path, format: ArchiveFormat, destination: &Path, strip_components: usize, paths: Option<&[String]>) -> Result<(), SyncError> {
+	let failed = |message: String| SyncError::Extract{ archive: archive.display().to_string(), message };
+
+	fs::create_dir_all(destination).context(IoSnafu{ context: format!("creating {:?}", destination) })?;
+	let file = BufReader::new(File::open(archive).context(IoSnafu{ context: format!("opening {:?}", archive) })?);
+
+	let reader: Box<dyn Read> = match format {
+		ArchiveFormat::TarGz => Box::new(flate2::read::GzDecoder::new(file)),
+		ArchiveFormat::TarXz => Box::new(xz2::read::XzDecoder::new(file)),
+		ArchiveFormat::TarBz2 => Box::new(bzip2::read::BzDecoder::new(file)),
+		ArchiveFormat::Zip => {
+			let mut zip = zip::ZipArchive::new(file).map_err(|error| failed(error.to_string()))?;
+			for i in 0..zip.len() {
+				let mut entry = zip.by_index(i).map_err(|error| failed(error.to_string()))?;
+				let name = entry.enclosed_name().ok_or_else(|| failed(format!("the entry {} points outside of the archive", entry.name())))?;
+				let Some(relative) = strip_path(&name, strip_components).map_err(failed)? else {
+					continue;
+				};
+				if !is_selected(&relative, paths) {
+					continue;
+				}
+
+				let path = destination.join(relative);
+				if entry.is_dir() {
+					fs::create_dir_all(&path).context(IoSnafu{ context: format!("creating {:?}", path) })?;
+					continue;
+				}
+
+				if let Some(parent) = path.parent() {
+					fs::create_dir_all(parent).context(IoSnafu{ context: format!("creating {:?}", parent) })?;
+				}
+				let mut output = File::create(&path).context(IoSnafu{ context: format!("creating {:?}", path) })?;
+				io::copy(&mut entry, &mut output).context(IoSnafu{ context: format!("extracting {:?}", path) })?;
+
+				// keep scripts like configure executable
+				#[cfg(unix)]
+				if let Some(mode) = entry.unix_mode() {
+					use std::os::unix::fs::PermissionsExt;
+					fs::set_permissions(&path, fs::Permissions::from_mode(mode & 0o777)).context(IoSnafu{ context: format!("setting the permissions of {:?}", path) })?;
+				}
+			}
+
+			return Ok(());
+		}
+	};
+
+	// symlinks are checked against the real path of the destination, it can be under a symlink itself
+	let root = destination.canonicalize().context(IoSnafu{ context: format!("grabbing full path of {:?}", destination) })?;
+
+	let mut tar = tar::Archive::new(reader);
+	for entry in tar.entries().context(IoSnafu{ context: format!("reading {:?}", archive) })? {
+		let mut entry = entry.context(IoSnafu{ context: format!("reading {:?}", archive) })?;
+		let name = entry.path().context(IoSnafu{ context: format!("reading {:?}", archive) })?.into_owned();
+		let Some(relative) = strip_path(&name, strip_components).map_err(failed)? else {
+			continue;
+		};
+		if !is_selected(&relative, paths) {
+			continue;
+		}
+
+		// a folder can be a symlink from an earlier entry, nothing is created or written through one that leads outside
+		let path = destination.join(&relative);
+		let parent = relative.parent().unwrap_or(Path::new(""));
+		let real_parent = resolve_inside(&root, &root, parent)
+			.ok_or_else(|| failed(format!("the entry {} is written through a link that points outside of the archive", name.display())))?;
+		fs::create_dir_all(&real_parent).context(IoSnafu{ context: format!("creating {:?}", real_parent) })?;
+
+		let link_name = entry.link_name().context(IoSnafu{ context: format!("reading {:?}", archive) })?.map(|target| target.into_owned());
+		match (entry.header().entry_type(), link_name) {
+			(tar::EntryType::Symlink, Some(target)) => {
+				if resolve_inside(&root, &real_parent, &target).is_none() {
+					return Err(failed(format!("the link {} points outside of the archive ({})", name.display(), target.display())));
+				}
+				entry.unpack(&path).context(IoSnafu{ context: format!("extracting {:?}", path) })?;
+			},
+			(tar::EntryType::Link, Some(target)) => {
+				// hard links name another entry of the archive, which is stripped the same way
+				let source = strip_path(&target, strip_components).map_err(failed)?
+					.map(|target| destination.join(target))
+					.filter(|source| source.canonicalize().is_ok_and(|source| source.starts_with(&root)))
+					.ok_or_else(|| failed(format!("the link {} points outside of the archive ({})", name.display(), target.display())))?;
+				let _ = fs::remove_file(&path);
+				fs::hard_link(&source, &path).context(IoSnafu{ context: format!("linking {:?} to {:?}", path, source) })?;
+			},
+			_ => entry.unpack(&path).map(|_| ()).context(IoSnafu{ context: format!("extracting {:?}", path) })?
+		}
+	}
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_archive_format() {
+        assert_eq!(ArchiveFormat::from_url("https://zlib.net/zlib-1.3.1.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_url("https://www.lua.org/ftp/lua-5.4.7.tar.gz?download=1"), Some(ArchiveFormat::TarGz));
+        assert_eq!(ArchiveFormat::from_url("https://example.com/foo.TAR.XZ"), Some(ArchiveFormat::TarXz));
+        assert_eq!(ArchiveFormat::from_url("https://example.com/foo-1.0.zip"), Some(ArchiveFormat::Zip));
+        assert_eq!(ArchiveFormat::from_url("https://github.com/gabime/spdlog.git"), None);
+    }
+
+    #[test]
+    fn check_archive_name() {
+        assert_eq!(archive_name("https://zlib.net/zlib-1.3.1.tar.gz"), "zlib-1.3.1.tar.gz");
+        assert_eq!(archive_name("https://example.com/files/libpng-1.6.43.tgz?x=1"), "libpng-1.6.43.tgz");
+    }
+
+    #[test]
+    fn check_strip_path() {
+        assert_eq!(strip_path(Path::new("zlib-1.3.1/src/zlib.h"), 1).unwrap(), Some(PathBuf::from("src/zlib.h")));
+        assert_eq!(strip_path(Path::new("zlib-1.3.1/"), 1).unwrap(), None);
+        assert!(strip_path(Path::new("zlib-1.3.1/../../etc/passwd"), 1).is_err());
+    }
+
+    #[test]
+    fn check_links() {
+        // a file written through a link that leads outside is rejected
+        let folder = std::env::temp_dir().join(format!("wares-links-{}", std::process::id()));
+        let outside = folder.join("outside");
+        fs::create_dir_all(outside.join("include")).unwrap();
+
+        let root = outside.canonicalize().unwrap();
+        assert!(resolve_inside(&root, &root.join("include"), Path::new("../src/zlib.h")).is_some());
+        assert!(resolve_inside(&root, &root, Path::new("/etc")).is_none());
+        assert!(resolve_inside(&root, &root.join("include"), Path::new("../../etc/passwd")).is_none());
+        assert!(resolve_inside(&root, &root, Path::new("src/../../etc")).is_none());
+
+        let tarball = |name: &str, links: &[(&str, &Path)]| {
+            let mut builder = tar::Builder::new(Vec::new());
+            for (link, target) in links {
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, link, target).unwrap();
+            }
+            let mut header = tar::Header::new_gnu();
+            header.set_size(1);
+            header.set_cksum();
+            builder.append_data(&mut header, "pkg/lib/x", &b"x"[..]).unwrap();
+
+            let path = folder.join(name);
+            let mut encoder = flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default());
+            encoder.write_all(&builder.into_inner().unwrap()).unwrap();
+            encoder.finish().unwrap();
+            path
+        };
+
+        let absolute = tarball("absolute.tar.gz", &[("pkg/lib", &outside)]);
+        assert!(extract(&absolute, ArchiveFormat::TarGz, &folder.join("absolute"), 1, None).is_err());
+
+        // each link looks like it stays inside, but together they lead to the folder above the destination
+        let relative = tarball("relative.tar.gz", &[("pkg/here", Path::new(".")), ("pkg/lib", Path::new("here/.."))]);
+        assert!(extract(&relative, ArchiveFormat::TarGz, &outside.join("relative"), 1, None).is_err());
+        assert!(!outside.join("x").exists());
+
+        // d/d is the destination itself on disk, so d/d/../.. is the folder above it
+        let chained = tarball("chained.tar.gz", &[("pkg/d", Path::new(".")), ("pkg/lib", Path::new("d/d/../.."))]);
+        assert!(extract(&chained, ArchiveFormat::TarGz, &outside.join("chained"), 1, None).is_err());
+        assert!(fs::symlink_metadata(outside.join("chained").join("lib")).is_err());
+        assert!(!outside.join("x").exists());
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
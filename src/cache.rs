@@ -1,12 +1,33 @@
+// standard libraries
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Write};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::fmt;
+
+// git2
+use git2::Repository;
+
+// versioning
 use semver::Version;
 
+// serialization/deserialization
 use serde::{Serialize, Deserialize};
 use serde::Serializer;
 use serde::ser::SerializeStruct;
-use serde::de::{Deserializer, Visitor};
-use std::fmt;
+use serde::de::{Deserializer, Visitor, Error};
 
-#[derive(Debug)]
+// hashing
+use sha2::{Sha256, Digest};
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::utils;
+use crate::{SyncError, IoSnafu, JsonSnafu};
+
+#[derive(Clone, Debug)]
 pub enum CachedObject {
 	Latest, // "latest"
 	Branch(String), // "<branch>"
@@ -26,7 +47,9 @@ pub enum CachedObject {
 	Rev {
 		rev: String,
 		hash: [u8; 20]
-	}
+	},
+	// { "archive": "<sha256>" }
+	Archive([u8; 32])
 }
 
 // serializer for CachedObject (see: CachedObject comments for layout)
@@ -56,6 +79,11 @@ impl Serialize for CachedObject {
     			rev_struct.serialize_field("rev", &rev)?;
     			rev_struct.serialize_field("hash", &crate::utils::format_hex::<20>(hash))?;
     			rev_struct.end()
+    		},
+    		CachedObject::Archive(sha256) => {
+    			let mut archive_struct = serializer.serialize_struct("archive", 1)?;
+    			archive_struct.serialize_field("archive", &crate::utils::format_hex::<32>(sha256))?;
+    			archive_struct.end()
     		}
     	}
     }
@@ -73,7 +101,8 @@ impl<'de> Visitor<'de> for CachedObjectVisitor {
         a string containing a commit hash
         an object with a version and hash key
         an object with a rev and hash key
-        an object with a tag and hash key")
+        an object with a tag and hash key
+        an object with an archive key")
     }
 
     fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
@@ -88,6 +117,54 @@ impl<'de> Visitor<'de> for CachedObjectVisitor {
        		Ok(CachedObject::Branch(s.to_string()))
        }
     }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+    	let mut semver: Option<Version> = None;
+    	let mut tag: Option<String> = None;
+    	let mut rev: Option<String> = None;
+    	let mut hash: Option<[u8; 20]> = None;
+    	let mut archive: Option<[u8; 32]> = None;
+
+    	while let Some(key) = access.next_key::<String>()? {
+    		match key.as_str() {
+    			"version" => semver = Some(access.next_value::<Version>()?),
+    			"tag" => tag = Some(access.next_value::<String>()?),
+    			"rev" => rev = Some(access.next_value::<String>()?),
+    			"archive" => {
+    				let value = access.next_value::<String>()?;
+    				if value.len() != 64 {
+    					return Err(A::Error::custom(format!("invalid sha256: {value}")));
+    				}
+    				archive = Some(crate::utils::parse_hex::<32>(&value).map_err(A::Error::custom)?);
+    			},
+    			"hash" => {
+    				let value = access.next_value::<String>()?;
+    				if !crate::utils::is_valid_hash(&value) {
+    					return Err(A::Error::custom(format!("invalid commit hash: {value}")));
+    				}
+    				hash = Some(crate::utils::parse_hex::<20>(&value).map_err(A::Error::custom)?);
+    			},
+    			_ => {
+    				access.next_value::<serde::de::IgnoredAny>()?;
+    			}
+    		}
+    	}
+
+    	if let Some(archive) = archive {
+    		return Ok(CachedObject::Archive(archive));
+    	}
+
+    	let hash = hash.ok_or_else(|| A::Error::missing_field("hash"))?;
+    	match (semver, tag, rev) {
+    		(Some(semver), None, None) => Ok(CachedObject::Version{ semver, hash }),
+    		(None, Some(tag), None) => Ok(CachedObject::Tag{ tag, hash }),
+    		(None, None, Some(rev)) => Ok(CachedObject::Rev{ rev, hash }),
+    		_ => Err(A::Error::custom("expected exactly one of the version, tag or rev keys"))
+    	}
+    }
 }
 
 impl<'de> Deserialize<'de> for CachedObject {
@@ -99,8 +176,421 @@ impl<'de> Deserialize<'de> for CachedObject {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+impl fmt::Display for CachedObject {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CachedObject::Latest => write!(f, "latest"),
+			CachedObject::Branch(branch) => write!(f, "branch {branch}"),
+			CachedObject::Commit(commit) => write!(f, "commit {:.7}", utils::format_hex::<20>(commit)),
+			CachedObject::Version{ semver, .. } => write!(f, "{semver}"),
+			CachedObject::Tag{ tag, .. } => write!(f, "tag {tag}"),
+			CachedObject::Rev{ rev, .. } => write!(f, "rev {rev}"),
+			CachedObject::Archive(sha256) => write!(f, "archive sha256 {:.12}", utils::format_hex::<32>(sha256))
+		}
+	}
+}
+
+// one checkout of a dependency in the cache
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CachedInstall {
+	pub folder: String, // name of the checkout's folder in the cache
+	pub object: CachedObject, // what was asked for
+	pub commit: String, // the commit that was checked out (or the sha256 of an archive)
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub patches: Option<String>, // the sha256 of the patches applied on top of it, if any
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub sparse: Option<Vec<String>>, // the only paths that were checked out, if it's a sparse checkout
+	pub installed: u64, // seconds since the unix epoch
+	pub last_used: u64 // seconds since the unix epoch
+}
+
+// every checkout of one repository in the cache
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct CachedDependency {
-	id: String,
-	installed: Vec<CachedObject>
-}
\ No newline at end of file
+	pub id: String,
+	pub url: String,
+	pub installed: Vec<CachedInstall>
+}
+
+// the index of the cache folder (index.json), the cache is made up of the checkouts recorded here
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct CacheIndex {
+	pub dependencies: BTreeMap<String, CachedDependency>
+}
+
+impl CacheIndex {
+	fn path(cache_folder: &Path) -> PathBuf {
+		cache_folder.join("index.json")
+	}
+
+	// reads the index of a cache folder, caches from before the index existed are indexed from their checkouts
+	pub fn load(cache_folder: &Path) -> Result<CacheIndex, SyncError> {
+		let index_file = CacheIndex::path(cache_folder);
+		if !index_file.exists() {
+			return CacheIndex::rebuild(cache_folder);
+		}
+
+		serde_json::from_reader(BufReader::new(File::open(&index_file).context(IoSnafu{ context: format!("opening {:?}", index_file) })?)).context(JsonSnafu)
+	}
+
+	// writes the index next to the old one and swaps them, so readers never see a half-written index
+	pub fn save(&self, cache_folder: &Path) -> Result<(), SyncError> {
+		let index_file = CacheIndex::path(cache_folder);
+		let temp_file = cache_folder.join(format!("index.json.{}.tmp", std::process::id()));
+
+		{
+			let mut writer = BufWriter::new(File::create(&temp_file).context(IoSnafu{ context: format!("creating {:?}", temp_file) })?);
+			serde_json::to_writer_pretty(&mut writer, self).context(JsonSnafu)?;
+			writer.flush().context(IoSnafu{ context: format!("writing {:?}", temp_file) })?;
+		}
+
+		fs::rename(&temp_file, &index_file).context(IoSnafu{ context: format!("replacing {:?}", index_file) })
+	}
+
+	// loads, changes and saves the index while holding its lock, so concurrent runs don't lose each other's changes
+	pub fn update<T, F: FnOnce(&mut CacheIndex) -> Result<T, SyncError>>(cache_folder: &Path, change: F) -> Result<T, SyncError> {
+		let _lock = CacheLock::acquire(cache_folder, "index")?;
+
+		let mut index = CacheIndex::load(cache_folder)?;
+		let result = change(&mut index)?;
+		index.save(cache_folder)?;
+
+		Ok(result)
+	}
+
+	// indexes every git checkout in the cache folder, without knowing what was originally asked for
+	fn rebuild(cache_folder: &Path) -> Result<CacheIndex, SyncError> {
+		let mut index = CacheIndex::default();
+
+		for entry in fs::read_dir(cache_folder).into_iter().flatten().flatten() {
+			// skip wares' own folders (.locks, .tmp)
+			if entry.file_name().to_string_lossy().starts_with('.') {
+				continue;
+			}
+
+			let Ok(repository) = Repository::open(entry.path()) else {
+				continue;
+			};
+			let Some(url) = repository.find_remote("origin").ok().and_then(|origin| origin.url().map(String::from)) else {
+				continue;
+			};
+			let Ok(head) = repository.head().and_then(|head| head.peel_to_commit()) else {
+				continue;
+			};
+
+			let folder = entry.file_name().to_string_lossy().to_string();
+			let installed = entry.metadata().ok()
+				.and_then(|metadata| metadata.modified().ok())
+				.map(utils::unix_time_of)
+				.unwrap_or(0);
+			let object = if folder.ends_with("-latest") {
+				CachedObject::Latest
+			} else {
+				CachedObject::Commit(head.id().as_bytes().try_into().expect("git2::Oid is always 20 bytes"))
+			};
+
+			// the patches are only known by the start of their hash
+			let patches = folder.split_once("-patched-").map(|(_, sha256)| sha256.to_string());
+
+			index.record(&crate::lock::package_id(&url), &url, CachedInstall { folder, object, commit: head.id().to_string(), patches, sparse: None, installed, last_used: installed });
+		}
+
+		Ok(index)
+	}
+
+	// adds (or replaces) a checkout
+	pub fn record(&mut self, id: &str, url: &str, install: CachedInstall) {
+		let dependency = self.dependencies.entry(id.to_string()).or_insert_with(|| CachedDependency { id: id.to_string(), url: url.to_string(), installed: Vec::new() });
+		dependency.installed.retain(|existing| existing.folder != install.folder);
+		dependency.installed.push(install);
+	}
+
+	// finds a checkout by its folder name
+	pub fn find(&self, folder: &str) -> Option<(&CachedDependency, &CachedInstall)> {
+		self.dependencies.values()
+			.find_map(|dependency| dependency.installed.iter().find(|install| install.folder == folder).map(|install| (dependency, install)))
+	}
+
+	// marks a checkout as used right now
+	pub fn touch(&mut self, folder: &str) {
+		let now = utils::unix_time();
+		for install in self.dependencies.values_mut().flat_map(|dependency| dependency.installed.iter_mut()) {
+			if install.folder == folder {
+				install.last_used = now;
+			}
+		}
+	}
+
+	// forgets a checkout, dropping the dependency once it has no checkouts left
+	pub fn remove(&mut self, folder: &str) -> Option<CachedInstall> {
+		let mut removed = None;
+		for dependency in self.dependencies.values_mut() {
+			if let Some(position) = dependency.installed.iter().position(|install| install.folder == folder) {
+				removed = Some(dependency.installed.remove(position));
+			}
+		}
+		self.dependencies.retain(|_, dependency| !dependency.installed.is_empty());
+		removed
+	}
+
+	// every checkout that was installed from url (or from the same repository over another protocol)
+	pub fn installs_of<'a>(&'a self, url: &'a str) -> impl Iterator<Item = &'a CachedInstall> {
+		let id = crate::lock::package_id(url);
+		self.dependencies.values()
+			.filter(move |dependency| dependency.url == url || dependency.id == id)
+			.flat_map(|dependency| dependency.installed.iter())
+	}
+}
+
+// what's wrong with a checkout in the cache
+#[derive(Debug)]
+pub enum CacheProblem {
+	Missing, // the folder is gone
+	NotARepository(git2::Error), // the folder isn't a readable git repository
+	WrongHead{ expected: String, found: String }, // HEAD isn't at the recorded commit
+	Dirty{ changes: usize } // files were modified, added or deleted since the checkout
+}
+
+impl fmt::Display for CacheProblem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			CacheProblem::Missing => write!(f, "the checkout is missing"),
+			CacheProblem::NotARepository(error) => write!(f, "the checkout isn't a git repository ({})", error.message()),
+			CacheProblem::WrongHead{ expected, found } => write!(f, "HEAD is at {found:.7}, expected {expected:.7}"),
+			CacheProblem::Dirty{ changes } => write!(f, "the worktree has {changes} modified file(s)")
+		}
+	}
+}
+
+// checks that a checkout is still exactly what was installed
+pub fn verify_install(cache_folder: &Path, install: &CachedInstall) -> Option<CacheProblem> {
+	let install_path = cache_folder.join(&install.folder);
+	if !install_path.exists() {
+		return Some(CacheProblem::Missing);
+	}
+
+	// extracted archives aren't repositories, there's nothing else to compare them against
+	if let CachedObject::Archive(_) = install.object {
+		return None;
+	}
+
+	let repository = match Repository::open(&install_path) {
+		Ok(repository) => repository,
+		Err(error) => return Some(CacheProblem::NotARepository(error))
+	};
+
+	let head = match repository.head().and_then(|head| head.peel_to_commit()) {
+		Ok(head) => head.id().to_string(),
+		Err(error) => return Some(CacheProblem::NotARepository(error))
+	};
+	if head != install.commit {
+		return Some(CacheProblem::WrongHead{ expected: install.commit.clone(), found: head });
+	}
+
+	// patched checkouts are supposed to differ from their commit
+	if install.patches.is_some() {
+		return None;
+	}
+
+	// files that were left out of a sparse checkout don't count as deleted
+	let mut status_options = git2::StatusOptions::new();
+	status_options.include_untracked(true).include_ignored(false);
+	for path in install.sparse.iter().flatten() {
+		status_options.pathspec(path);
+	}
+	let changes = match repository.statuses(Some(&mut status_options)) {
+		Ok(statuses) => statuses.len(),
+		Err(error) => return Some(CacheProblem::NotARepository(error))
+	};
+
+	if changes > 0 {
+		Some(CacheProblem::Dirty{ changes })
+	} else {
+		None
+	}
+}
+
+// deletes a checkout from the disk and the index
+pub fn remove_install(cache_folder: &Path, folder: &str) -> Result<(), SyncError> {
+	// wait for anyone still installing the checkout
+	let _lock = CacheLock::acquire(cache_folder, folder)?;
+
+	// forget the checkout first, a crash after this leaves an unindexed folder that gets cleaned up on the next install
+	CacheIndex::update(cache_folder, |index| {
+		index.remove(folder);
+		Ok(())
+	})?;
+
+	let install_path = cache_folder.join(folder);
+	if install_path.exists() {
+		utils::remove_dir_all(&install_path).context(IoSnafu{ context: format!("removing {:?}", install_path) })?;
+	}
+
+	Ok(())
+}
+
+// an exclusive, advisory lock on an entry of the cache (.locks/<name>.lock), released when dropped
+pub struct CacheLock {
+	file: File
+}
+
+impl CacheLock {
+	// blocks until no other process holds the lock
+	pub fn acquire(cache_folder: &Path, name: &str) -> Result<CacheLock, SyncError> {
+		let locks_folder = cache_folder.join(".locks");
+		fs::create_dir_all(&locks_folder).context(IoSnafu{ context: format!("creating {:?}", locks_folder) })?;
+
+		let lock_file = locks_folder.join(format!("{name}.lock"));
+		let file = fs::OpenOptions::new().create(true).truncate(false).write(true).open(&lock_file).context(IoSnafu{ context: format!("opening {:?}", lock_file) })?;
+		file.lock().context(IoSnafu{ context: format!("locking {:?}", lock_file) })?;
+
+		Ok(CacheLock { file })
+	}
+}
+
+impl Drop for CacheLock {
+	fn drop(&mut self) {
+		let _ = self.file.unlock();
+	}
+}
+
+// an empty folder (.tmp/<folder>-<pid>) to install a checkout into before it's moved into place
+// the caller has to hold the checkout's lock, which means every other temporary folder for it was left by a crashed run
+pub fn temp_path(cache_folder: &Path, folder: &str) -> Result<PathBuf, SyncError> {
+	let temp_folder = cache_folder.join(".tmp");
+	fs::create_dir_all(&temp_folder).context(IoSnafu{ context: format!("creating {:?}", temp_folder) })?;
+
+	let prefix = format!("{folder}-");
+	for entry in fs::read_dir(&temp_folder).context(IoSnafu{ context: format!("reading {:?}", temp_folder) })?.flatten() {
+		let name = entry.file_name().to_string_lossy().to_string();
+		if name.strip_prefix(&prefix).is_some_and(|pid| pid.chars().all(|c| c.is_ascii_digit())) {
+			utils::remove_dir_all(&entry.path()).context(IoSnafu{ context: format!("removing the partial install {:?}", entry.path()) })?;
+		}
+	}
+
+	Ok(temp_folder.join(format!("{folder}-{}", std::process::id())))
+}
+
+impl CacheIndex {
+	// the dependencies a query refers to, either by id (gh-gabime-spdlog), url or repository name (spdlog)
+	pub fn matching(&self, query: &str) -> Vec<&CachedDependency> {
+		self.dependencies.values()
+			.filter(|dependency| {
+				let repository_name = dependency.url.trim_end_matches('/').trim_end_matches(".git").rsplit(['/', ':']).next().unwrap_or("");
+				dependency.id == query || dependency.url == query || repository_name == query
+			})
+			.collect()
+	}
+
+	// the checkouts that haven't been used since the given time (in seconds since the unix epoch)
+	pub fn unused_since(&self, time: u64) -> Vec<&CachedInstall> {
+		self.dependencies.values()
+			.flat_map(|dependency| dependency.installed.iter())
+			.filter(|install| install.last_used < time)
+			.collect()
+	}
+}
+
+// hashes every file in a checkout except git's own, so any change to what gets built can be detected
+// (i.e. a force-pushed tag or a tampered mirror), the result looks like sha256-<hex>
+pub fn content_hash(folder: &Path) -> Result<String, SyncError> {
+	fn collect(folder: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> Result<(), SyncError> {
+		for entry in fs::read_dir(folder).context(IoSnafu{ context: format!("reading {:?}", folder) })? {
+			let entry = entry.context(IoSnafu{ context: format!("reading {:?}", folder) })?;
+			let name = entry.file_name().to_string_lossy().to_string();
+			if name == ".git" {
+				continue;
+			}
+
+			// always use / so the hash is the same on every platform
+			let relative = format!("{prefix}{name}");
+			let file_type = entry.file_type().context(IoSnafu{ context: format!("reading {:?}", entry.path()) })?;
+			if file_type.is_dir() {
+				collect(&entry.path(), &format!("{relative}/"), files)?;
+			} else {
+				files.push((relative, entry.path()));
+			}
+		}
+		Ok(())
+	}
+
+	let mut files = Vec::new();
+	collect(folder, "", &mut files)?;
+	files.sort();
+
+	let mut hasher = Sha256::new();
+	for (relative, path) in files {
+		let metadata = fs::symlink_metadata(&path).context(IoSnafu{ context: format!("reading {:?}", path) })?;
+		let (kind, contents) = if metadata.is_symlink() {
+			("link", fs::read_link(&path).context(IoSnafu{ context: format!("reading {:?}", path) })?.to_string_lossy().as_bytes().to_vec())
+		} else {
+			("file", fs::read(&path).context(IoSnafu{ context: format!("reading {:?}", path) })?)
+		};
+
+		hasher.update(kind.as_bytes());
+		hasher.update(relative.as_bytes());
+		hasher.update([0]);
+		hasher.update((contents.len() as u64).to_le_bytes());
+		hasher.update(&contents);
+	}
+
+	let digest: [u8; 32] = hasher.finalize().into();
+	Ok(format!("sha256-{}", utils::format_hex(&digest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_cached_object_round_trip() {
+        let objects = vec![
+            CachedObject::Latest,
+            CachedObject::Branch(String::from("docking")),
+            CachedObject::Commit([7; 20]),
+            CachedObject::Version{ semver: Version::new(1, 14, 1), hash: [1; 20] },
+            CachedObject::Tag{ tag: String::from("v1.0"), hash: [2; 20] },
+            CachedObject::Rev{ rev: String::from("refs/pull/1/head"), hash: [3; 20] },
+            CachedObject::Archive([4; 32])
+        ];
+
+        for object in objects {
+            let json = serde_json::to_string(&object).unwrap();
+            let parsed: CachedObject = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn check_archives_with_the_same_name() {
+        let first = "https://github.com/madler/zlib/archive/refs/tags/v1.0.tar.gz";
+        let second = "https://github.com/glennrp/libpng/archive/refs/tags/v1.0.tar.gz";
+        assert_ne!(crate::lock::package_id(first), crate::lock::package_id(second));
+
+        let mut index = CacheIndex::default();
+        let install = CachedInstall { folder: String::from("zlib"), object: CachedObject::Archive([4; 32]), commit: String::from("04"), patches: None, sparse: None, installed: 0, last_used: 0 };
+        index.record(&crate::lock::package_id(first), first, install);
+        assert_eq!(index.installs_of(first).count(), 1);
+        assert_eq!(index.installs_of(second).count(), 0);
+    }
+
+    #[test]
+    fn check_content_hash() {
+        let folder = std::env::temp_dir().join(format!("wares-content-hash-{}", std::process::id()));
+        fs::create_dir_all(folder.join("include")).unwrap();
+        fs::create_dir_all(folder.join(".git")).unwrap();
+        fs::write(folder.join("include/lib.h"), "int lib();").unwrap();
+
+        let original = content_hash(&folder).unwrap();
+        assert!(original.starts_with("sha256-"));
+
+        // git's own files don't count
+        fs::write(folder.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        assert_eq!(content_hash(&folder).unwrap(), original);
+
+        fs::write(folder.join("include/lib.h"), "int lib(void);").unwrap();
+        assert_ne!(content_hash(&folder).unwrap(), original);
+
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+// lints a project's wares.toml (wares check)
+//
+// the manifest is parsed strictly, so typos, conflicting specifiers and unknown groups are already errors. on
+// top of that this warns about the things that parse but are likely mistakes: dependencies that follow the
+// default branch, repositories that are pulled in twice under different names and groups no build script asks
+// for.
+
+// standard libraries
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+// internal dependencies
+use crate::doctor::{Check, Status};
+use crate::init::BuildSystem;
+use crate::lock;
+use crate::manifest::{ManifestFile, Specifier};
+use crate::SyncError;
+
+// the lints of a manifest, build_scripts are the (name, contents) of the build scripts next to it
+pub fn lint(manifest: &ManifestFile, build_scripts: &[(String, String)]) -> Vec<Check> {
+	let mut checks = Vec::new();
+
+	// floating dependencies lock to whatever the default branch is at when they're locked
+	for (group, dependencies) in &manifest.dependencies {
+		for dependency in dependencies.iter().filter(|dependency| matches!(dependency.specifier(), Specifier::MainBranch)) {
+			checks.push(Check::new(&format!("{} [{group}]", dependency.name), Status::Warn, "follows the default branch, pin it to a version, tag or commit so every lock gets the same code"));
+		}
+	}
+
+	// the same repository under two names is installed (and built) twice, over any protocol
+	let mut names: BTreeMap<String, Vec<(&str, &str)>> = BTreeMap::new();
+	for (group, dependencies) in &manifest.dependencies {
+		for dependency in dependencies {
+			let entries = names.entry(lock::id_of(dependency.repo_url(), true)).or_default();
+			if !entries.iter().any(|(name, _)| *name == dependency.name) {
+				entries.push((&dependency.name, group));
+			}
+		}
+	}
+
+	for (id, entries) in names.iter().filter(|(_, entries)| entries.len() > 1) {
+		let names: Vec<String> = entries.iter().map(|(name, group)| format!("{name} [{group}]")).collect();
+		checks.push(Check::new(id, Status::Warn, format!("is a dependency under more than one name: {}, use a single name so it's only installed once", names.join(", "))));
+	}
+
+	// dependencies is always installed, the other groups only when a build script (or wares sync) asks for them
+	if !build_scripts.is_empty() {
+		let script_names: Vec<&str> = build_scripts.iter().map(|(name, _)| name.as_str()).collect();
+		for group in manifest.dependencies.keys().filter(|group| *group != "dependencies") {
+			if !build_scripts.iter().any(|(_, contents)| contents.contains(group.as_str())) {
+				checks.push(Check::new(&format!("[{group}]"), Status::Warn, format!("isn't referenced by {}, it's only installed by wares sync {group}", script_names.join(" or "))));
+			}
+		}
+	}
+
+	checks
+}
+
+// reads the manifest and the build scripts next to it and lints them
+pub fn check(manifest_file: &Path) -> Result<Vec<Check>, SyncError> {
+	let manifest = crate::read_manifest(manifest_file)?;
+
+	let folder = crate::folder_of(manifest_file)?;
+	let build_scripts: Vec<(String, String)> = [BuildSystem::CMake, BuildSystem::Premake].iter()
+		.filter_map(|build_system| {
+			let name = build_system.build_script();
+			fs::read_to_string(folder.join(name)).ok().map(|contents| (name.to_string(), contents))
+		})
+		.collect();
+
+	let mut checks = lint(&manifest, &build_scripts);
+	if checks.is_empty() {
+		let count = manifest.dependencies.values().map(|dependencies| dependencies.len()).sum::<usize>();
+		checks.push(Check::new("wares.toml", Status::Pass, format!("no problems found ({count} dependencies in {} group(s))", manifest.dependencies.len())));
+	}
+
+	Ok(checks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_lint() {
+        let manifest = ManifestFile::parse("manifest_version = 1\n[dependencies]\nfmt = \"gh:fmtlib/fmt@^11\"\nimgui = \"gh:ocornut/imgui\"\n[dev-dependencies]\nlibfmt = \"git:https://github.com/fmtlib/fmt.git#11.0.0\"\nfmt = \"gh:fmtlib/fmt@^11\"\n[test-dependencies]\ndoctest = \"gh:doctest/doctest@^2\"\n").unwrap();
+        let scripts = vec![(String::from("premake5.lua"), String::from("local deps = wares.sync({ [\"dev-dependencies\"] = true })\n"))];
+        let subjects: Vec<String> = lint(&manifest, &scripts).into_iter().map(|check| check.subject).collect();
+        assert_eq!(subjects, vec!["imgui [dependencies]", "gh-fmtlib-fmt", "[test-dependencies]"]);
+
+        // without a build script there's nothing to say which groups are used
+        assert_eq!(lint(&manifest, &[]).len(), 2);
+    }
+}
@@ -0,0 +1,311 @@
+// checks an installation of wares and a project for problems (wares doctor)
+//
+// every check passes, warns (something that the next sync fixes or that's worth knowing) or fails (something
+// that has to be fixed by hand). checks that depend on a file that couldn't be read are skipped.
+
+// standard libraries
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+// regular expressions
+use regex::Regex;
+
+// serialization/deserialization
+use serde::Serialize;
+
+// internal dependencies
+use crate::init::{self, BuildSystem};
+use crate::lock::LockFile;
+use crate::manifest::{ManifestFile, RefSource, Specifier};
+use crate::utils;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+	Pass,
+	Warn,
+	Fail
+}
+
+// one line of the checklist
+#[derive(Serialize, Debug)]
+pub struct Check {
+	// what was checked (wares.toml, the cache, a package or a remote)
+	pub subject: String,
+	pub status: Status,
+	pub message: String
+}
+
+impl Check {
+	pub(crate) fn new(subject: &str, status: Status, message: impl Into<String>) -> Check {
+		Check { subject: subject.to_string(), status, message: message.into() }
+	}
+}
+
+// the lock file against the manifest: every dependency is locked to something its requirement allows, and
+// everything that's locked is needed
+pub fn consistency(manifest: &ManifestFile, lockfile: &LockFile) -> Vec<Check> {
+	let mut checks = Vec::new();
+
+	for (group, dependencies) in &manifest.dependencies {
+		for dependency in dependencies {
+			let subject = format!("{} [{group}]", dependency.name);
+			let Some(locked) = lockfile.dependencies.get(&dependency.name) else {
+				checks.push(Check::new(&subject, Status::Warn, "isn't in wares.lock, the next sync of this group locks it"));
+				continue;
+			};
+
+			if !matches!(dependency.specifier(), Specifier::Path) && locked.url() != dependency.repo_url() {
+				checks.push(Check::new(&subject, Status::Fail, format!("wares.lock locks it from {}, but wares.toml asks for {} (run wares update {})", locked.url(), dependency.repo_url(), dependency.name)));
+			} else if let Specifier::Version(requirement) = dependency.specifier() {
+				if !locked.version.as_ref().is_some_and(|version| requirement.matches(version)) {
+					checks.push(Check::new(&subject, Status::Fail, format!("wares.lock locks it to {locked}, which {requirement} doesn't allow (run wares update {})", dependency.name)));
+				}
+			}
+		}
+	}
+
+	for (name, locked) in &lockfile.dependencies {
+		for child in &locked.dependencies {
+			if !lockfile.dependencies.contains_key(child) {
+				checks.push(Check::new(name, Status::Fail, format!("depends on {child}, which isn't in wares.lock (run wares update)")));
+			}
+		}
+	}
+
+	let roots: Vec<&str> = manifest.dependencies.values().flatten().map(|dependency| dependency.name.as_str()).collect();
+	let needed = lockfile.closure(&roots);
+	for name in lockfile.dependencies.keys().filter(|name| !needed.contains(name)) {
+		checks.push(Check::new(name, Status::Warn, "is in wares.lock, but nothing in wares.toml needs it anymore"));
+	}
+
+	if checks.is_empty() {
+		checks.push(Check::new("wares.lock", Status::Pass, format!("matches wares.toml ({} package(s) locked)", lockfile.dependencies.len())));
+	}
+	checks
+}
+
+// the cache folder exists (or can be created) and can be written to
+fn check_cache(cache_folder: &Path) -> Check {
+	let subject = "cache";
+	if !cache_folder.exists() {
+		return Check::new(subject, Status::Warn, format!("{} doesn't exist yet, the first sync creates it", cache_folder.display()));
+	}
+
+	let folder = match utils::get_full_path(cache_folder) {
+		Ok(folder) => folder,
+		Err(error) => return Check::new(subject, Status::Fail, format!("{} can't be resolved ({error})", cache_folder.display()))
+	};
+
+	let probe = folder.join(format!(".wares-doctor-{}", std::process::id()));
+	match fs::write(&probe, b"") {
+		Ok(()) => {
+			let _ = fs::remove_file(&probe);
+			Check::new(subject, Status::Pass, format!("{} is writable", folder.display()))
+		},
+		Err(error) => Check::new(subject, Status::Fail, format!("{} can't be written to ({error})", folder.display()))
+	}
+}
+
+// every locked dependency is installed, at the commit it's locked to
+fn check_installs(lockfile: &LockFile, cache_folder: &Path) -> Vec<Check> {
+	lockfile.dependencies.iter().map(|(name, locked)| {
+		let folder = locked.install_folder(cache_folder);
+		if locked.is_path() {
+			return match folder.is_dir() {
+				true => Check::new(name, Status::Pass, format!("uses {}", folder.display())),
+				false => Check::new(name, Status::Fail, format!("the folder {} is missing", folder.display()))
+			};
+		}
+
+		let root = utils::get_full_path(cache_folder).map(|cache| cache.join(locked.uuid()));
+		match (root.as_ref().map(|root| root.exists()), locked.installed_commit(cache_folder)) {
+			(Ok(true), Some(_)) => Check::new(name, Status::Pass, format!("{locked} is installed in {}", folder.display())),
+			(Ok(true), None) => Check::new(name, Status::Fail, format!("the checkout in {} isn't {locked} (remove it with wares cache clean {name})", folder.display())),
+			_ => Check::new(name, Status::Warn, format!("{locked} isn't installed, the next sync installs it"))
+		}
+	}).collect()
+}
+
+// every remote in the manifest and the lock file answers
+fn check_remotes(manifest: Option<&ManifestFile>, lockfile: Option<&LockFile>) -> Vec<Check> {
+	// (url, is an archive)
+	let mut remotes: Vec<(String, bool)> = Vec::new();
+	for dependency in manifest.iter().flat_map(|manifest| manifest.dependencies.values().flatten()) {
+		if !matches!(dependency.specifier(), Specifier::Path) {
+			remotes.push((dependency.repo_url().to_string(), matches!(dependency.specifier(), Specifier::Archive{ .. })));
+		}
+	}
+	for locked in lockfile.iter().flat_map(|lockfile| lockfile.dependencies.values()).filter(|locked| !locked.is_path()) {
+		remotes.push((locked.url().to_string(), locked.has_fixed_contents() && locked.oid().is_none()));
+	}
+	remotes.sort();
+	remotes.dedup();
+
+	utils::parallel_map(&remotes, utils::jobs_fallback(), |(url, archive)| {
+		let result = match archive {
+			true => ureq::head(url).call().map(|_| ()).map_err(|error| error.to_string()),
+			false => RefSource::Remote.list(url).map(|_| ()).map_err(|error| error.to_string())
+		};
+
+		match result {
+			Ok(()) => Check::new(url, Status::Pass, "is reachable"),
+			Err(error) => Check::new(url, Status::Fail, format!("can't be reached ({error})"))
+		}
+	})
+}
+
+// the version written into a get_wares script
+fn script_version(script: &str) -> Option<String> {
+	static VERSION_REGEX: OnceLock<Regex> = OnceLock::new();
+	let version_regex = VERSION_REGEX.get_or_init(|| Regex::new(r#"(?:set\(WARES_VERSION|local version =)\s*"([^"]+)""#).unwrap());
+	version_regex.captures(script).map(|captures| captures[1].to_string())
+}
+
+// the version a premake module (wares_native) was built from, see WARES_NATIVE_VERSION
+// (the marker can show up elsewhere in the library too, i.e. in this function, so only one followed by a version counts)
+fn native_version(library: &[u8]) -> Option<String> {
+	let marker = b"wares_native version ";
+	library.windows(marker.len())
+		.enumerate()
+		.filter(|(_, window)| window == marker)
+		.find_map(|(start, _)| {
+			let version = &library[start + marker.len()..];
+			let end = version.iter().position(|byte| *byte == 0)?;
+			let version = std::str::from_utf8(&version[..end]).ok()?;
+			semver::Version::parse(version).ok().map(|version| version.to_string())
+		})
+}
+
+// the cmake and premake scripts of the project (and the premake module) are the ones this version of wares expects
+fn check_scripts(folder: &Path) -> Vec<Check> {
+	let expected = env!("CARGO_PKG_VERSION");
+	let mut checks = Vec::new();
+
+	for build_system in [BuildSystem::CMake, BuildSystem::Premake] {
+		let get_wares = build_system.get_wares();
+		if let Ok(script) = fs::read_to_string(folder.join(get_wares)) {
+			checks.push(match script_version(&script) {
+				Some(version) if version == expected => Check::new(get_wares, Status::Pass, format!("installs wares {version}")),
+				Some(version) => Check::new(get_wares, Status::Warn, format!("installs wares {version}, but this is wares {expected} (run wares init again after removing it)")),
+				None => Check::new(get_wares, Status::Warn, "doesn't say which version of wares it installs")
+			});
+		}
+
+		let module = build_system.module_path();
+		if let Ok(contents) = fs::read_to_string(folder.join(module)) {
+			checks.push(match init::module_hash(&contents) == init::module_hash(build_system.module()) {
+				true => Check::new(module, Status::Pass, format!("is the one wares {expected} expects")),
+				false => Check::new(module, Status::Warn, format!("isn't the one wares {expected} expects (remove it and {get_wares} downloads it again)"))
+			});
+		}
+	}
+
+	for library in ["wares_native.dll", "libwares_native.so", "wares_native.so", "libwares_native.dylib", "wares_native.dylib"] {
+		let file = folder.join("wares").join(library);
+		let Ok(contents) = fs::read(&file) else {
+			continue;
+		};
+
+		let subject = format!("wares/{library}");
+		checks.push(match native_version(&contents) {
+			Some(version) if version == expected => Check::new(&subject, Status::Pass, format!("was built from wares {version}")),
+			Some(version) => Check::new(&subject, Status::Warn, format!("was built from wares {version}, but this is wares {expected}")),
+			None => Check::new(&subject, Status::Warn, format!("was built from a version of wares older than {expected}"))
+		});
+	}
+
+	checks
+}
+
+// runs every check, the network is only used if online is set
+pub fn diagnose(manifest_file: &Path, lock_file: &Path, cache_folder: &Path, online: bool) -> Vec<Check> {
+	let mut checks = Vec::new();
+
+	let manifest = match crate::read_manifest(manifest_file) {
+		Ok(manifest) => {
+			let count = manifest.dependencies.values().map(|dependencies| dependencies.len()).sum::<usize>();
+			checks.push(Check::new("wares.toml", Status::Pass, format!("parses ({count} dependencies in {} group(s))", manifest.dependencies.len())));
+			Some(manifest)
+		},
+		Err(error) => {
+			checks.push(Check::new("wares.toml", Status::Fail, error.to_string()));
+			None
+		}
+	};
+
+	let lockfile = if lock_file.exists() {
+		match crate::read_lockfile(lock_file) {
+			Ok(lockfile) => {
+				checks.push(Check::new("wares.lock", Status::Pass, "parses"));
+				Some(lockfile)
+			},
+			Err(error) => {
+				checks.push(Check::new("wares.lock", Status::Fail, format!("{error} (remove it and run wares sync)")));
+				None
+			}
+		}
+	} else {
+		checks.push(Check::new("wares.lock", Status::Warn, "doesn't exist yet, the first sync writes it"));
+		None
+	};
+
+	if let (Some(manifest), Some(lockfile)) = (&manifest, &lockfile) {
+		checks.extend(consistency(manifest, lockfile));
+	}
+
+	checks.push(check_cache(cache_folder));
+	if let Some(lockfile) = &lockfile {
+		checks.extend(check_installs(lockfile, cache_folder));
+	}
+
+	if online {
+		checks.extend(check_remotes(manifest.as_ref(), lockfile.as_ref()));
+	} else {
+		checks.push(Check::new("network", Status::Warn, "skipped the remotes while offline"));
+	}
+
+	if let Ok(folder) = crate::folder_of(manifest_file) {
+		checks.extend(check_scripts(&folder));
+	}
+
+	checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::{LockedDependency, LockedDependencyId};
+    use semver::Version;
+
+    #[test]
+    fn check_consistency() {
+        let manifest = ManifestFile::parse("manifest_version = 1\n[dependencies]\nfmt = \"git:https://example.com/fmt.git@^10\"\nglm = \"git:https://example.com/glm.git\"\n").unwrap();
+
+        let mut lockfile = LockFile::new();
+        let mut fmt = LockedDependency::new(String::from("https://example.com/fmt.git"), LockedDependencyId::Oid(git2::Oid::zero()));
+        fmt.version = Some(Version::new(10, 2, 1));
+        lockfile.dependencies.insert(String::from("fmt"), fmt.clone());
+        lockfile.dependencies.insert(String::from("glm"), LockedDependency::new(String::from("https://example.com/glm.git"), LockedDependencyId::MainBranch));
+
+        let checks = consistency(&manifest, &lockfile);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, Status::Pass);
+
+        fmt.version = Some(Version::new(9, 1, 0));
+        lockfile.dependencies.insert(String::from("fmt"), fmt);
+        lockfile.dependencies.insert(String::from("zlib"), LockedDependency::new(String::from("https://example.com/zlib.git"), LockedDependencyId::MainBranch));
+
+        let checks: Vec<(String, Status)> = consistency(&manifest, &lockfile).into_iter().map(|check| (check.subject, check.status)).collect();
+        assert_eq!(checks, vec![(String::from("fmt [dependencies]"), Status::Fail), (String::from("zlib"), Status::Warn)]);
+    }
+
+    #[test]
+    fn check_versions() {
+        assert_eq!(script_version("set(WARES_VERSION \"0.1.0\")\n").as_deref(), Some("0.1.0"));
+        assert_eq!(script_version("local version = \"0.2.0\"\n").as_deref(), Some("0.2.0"));
+        assert_eq!(native_version(b"\x7fELF...wares_native version \x01\x02\0wares_native version 0.1.0\0...").as_deref(), Some("0.1.0"));
+        assert_eq!(native_version(b"\x7fELF..."), None);
+    }
+}
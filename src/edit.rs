@@ -0,0 +1,220 @@
+// changes to wares.toml made from the command line (wares add and wares remove)
+//
+// the manifest is edited with toml_edit instead of being parsed and written again, so the comments,
+// ordering and formatting the user wrote are kept.
+
+// standard libraries
+use std::fs;
+use std::path::Path;
+
+// versioning
+use semver::Version;
+
+// serialization/deserialization
+use toml::Value;
+use toml_edit::DocumentMut;
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::{forge, outdated};
+use crate::archive::ArchiveFormat;
+use crate::manifest::{ManifestDependency, Specifier, RefSource, LockingError};
+use crate::{SyncError, IoSnafu, LockSnafu, SpecSnafu, ManifestEditSnafu, NotInManifestSnafu};
+
+// a dependency that was written to the manifest
+#[derive(Debug)]
+pub struct Added {
+	pub group: String,
+	pub name: String,
+	// the dependency string as it was written (with the version it was pinned to)
+	pub spec: String
+}
+
+// the name a dependency gets when none is given: the repository, folder or archive name (without its version)
+pub fn default_name(dependency: &ManifestDependency) -> String {
+	let url = dependency.repo_url().trim_end_matches(['/', '\\']);
+
+	match dependency.specifier() {
+		Specifier::Path => url.rsplit(['/', '\\']).next().unwrap_or(url).to_string(),
+		Specifier::Archive{ .. } => {
+			// zlib-1.3.1.tar.gz => zlib
+			let file = url.split(['?', '#']).next().unwrap_or(url);
+			let file = file.rsplit('/').next().unwrap_or(file);
+			let stem = match ArchiveFormat::from_url(file) {
+				Some(format) => file.strip_suffix(&format!(".{}", format.extension())).or(file.rsplit_once('.').map(|(stem, _)| stem)).unwrap_or(file),
+				None => file
+			};
+
+			match stem.char_indices().find(|(i, c)| *c == '-' && stem[i + 1..].starts_with(|c: char| c.is_ascii_digit())) {
+				Some((i, _)) if i > 0 => stem[..i].to_string(),
+				_ => stem.to_string()
+			}
+		},
+		_ => {
+			let location = forge::repository_location(url);
+			location.rsplit('/').next().unwrap_or(&location).to_string()
+		}
+	}
+}
+
+// the newest release of a repository (pre-releases only count if there's nothing else)
+fn latest_version(dependency: &ManifestDependency) -> Result<Option<Version>, LockingError> {
+	let versions = dependency.versions(RefSource::Remote)?;
+	Ok(outdated::newest(versions.keys()).cloned())
+}
+
+// adds name = "spec" to a group of the manifest's text, returning the new text
+pub fn insert(manifest: &str, group: &str, name: &str, spec: &str) -> Result<String, SyncError> {
+	let mut document: DocumentMut = manifest.parse().context(ManifestEditSnafu)?;
+
+	let table = document.entry(group)
+		.or_insert(toml_edit::table())
+		.as_table_like_mut()
+		.ok_or_else(|| SyncError::ManifestGroup{ group: group.to_string() })?;
+
+	if table.contains_key(name) {
+		return Err(SyncError::DependencyExists{ group: group.to_string(), name: name.to_string() });
+	}
+	table.insert(name, toml_edit::value(spec));
+
+	Ok(document.to_string())
+}
+
+// checks a dependency string, makes sure it can be locked and writes it to the manifest
+// dependencies without a specifier are pinned to ^<the latest release>, if the repository has any
+pub fn add(manifest_file: &Path, spec: &str, group: &str, name: Option<&str>) -> Result<Added, SyncError> {
+	// before anything goes over the network
+	if !crate::manifest::is_group_name(group) {
+		return Err(SyncError::ManifestGroup{ group: group.to_string() });
+	}
+
+	let contents = fs::read_to_string(manifest_file).context(IoSnafu{ context: format!("reading {:?}", manifest_file) })?;
+
+	let mut dependency = ManifestDependency::parse(&Value::String(spec.to_string())).context(SpecSnafu{ spec })?;
+	dependency.resolve_paths(&crate::folder_of(manifest_file)?);
+
+	let mut spec = spec.to_string();
+	match dependency.specifier() {
+		Specifier::Version(requirement) => {
+			if dependency.matching_versions(&dependency.versions(RefSource::Remote).context(LockSnafu)?).is_empty() {
+				return Err(LockingError::NoMatch{ requirement: requirement.to_string() }).context(LockSnafu);
+			}
+		},
+		Specifier::MainBranch => {
+			if let Some(version) = latest_version(&dependency).context(LockSnafu)? {
+				spec = format!("{spec}@^{version}");
+			}
+		},
+		_ => {
+			dependency.lock(RefSource::Remote).context(LockSnafu)?;
+		}
+	}
+
+	let name = name.map(String::from).unwrap_or_else(|| default_name(&dependency));
+	let edited = insert(&contents, group, &name, &spec)?;
+	fs::write(manifest_file, edited).context(IoSnafu{ context: format!("writing {:?}", manifest_file) })?;
+
+	Ok(Added { group: group.to_string(), name, spec })
+}
+
+// a dependency that was taken out of the manifest
+#[derive(Debug)]
+pub struct Removed {
+	// the groups it was in
+	pub groups: Vec<String>,
+	// the packages that were dropped from the lock file (it and the dependencies only it needed)
+	pub unlocked: Vec<String>
+}
+
+// removes a dependency from every group of the manifest's text, returning the new text and the groups it was in
+pub fn delete(manifest: &str, name: &str) -> Result<(String, Vec<String>), SyncError> {
+	let mut document: DocumentMut = manifest.parse().context(ManifestEditSnafu)?;
+
+	let mut groups = Vec::new();
+	for (group, table) in document.iter_mut() {
+		if let Some(table) = table.as_table_like_mut() {
+			if table.remove(name).is_some() {
+				groups.push(group.get().to_string());
+			}
+		}
+	}
+
+	Ok((document.to_string(), groups))
+}
+
+// removes a dependency from the manifest, and from the lock file unless another package still depends on it
+pub fn remove(manifest_file: &Path, lock_file: &Path, name: &str) -> Result<Removed, SyncError> {
+	let contents = fs::read_to_string(manifest_file).context(IoSnafu{ context: format!("reading {:?}", manifest_file) })?;
+	let (edited, groups) = delete(&contents, name)?;
+	if groups.is_empty() {
+		return NotInManifestSnafu{ name }.fail();
+	}
+
+	// the packages the manifest still asks for, in any group
+	let manifest = crate::parse_manifest(manifest_file, &edited)?;
+	let roots: Vec<&str> = manifest.dependencies.values().flatten().map(|dependency| dependency.name.as_str()).collect();
+
+	fs::write(manifest_file, &edited).context(IoSnafu{ context: format!("writing {:?}", manifest_file) })?;
+
+	let mut unlocked = Vec::new();
+	if lock_file.exists() {
+		let mut lockfile = crate::read_lockfile(lock_file)?;
+
+		let mut queue = vec![name.to_string()];
+		while let Some(package) = queue.pop() {
+			let needed = roots.contains(&package.as_str()) || lockfile.dependencies.values().any(|dependency| dependency.dependencies.contains(&package));
+			if needed {
+				continue;
+			}
+
+			if let Some(removed) = lockfile.dependencies.remove(&package) {
+				queue.extend(removed.dependencies);
+				unlocked.push(package);
+			}
+		}
+
+		crate::write_lockfile(lock_file, &lockfile)?;
+	}
+
+	Ok(Removed { groups, unlocked })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_insert() {
+        let manifest = "manifest_version = 1\n\n# rendering\n[dependencies]\nglfw = \"gh:glfw/glfw@^3.4\" # windowing\n\n[dev-dependencies]\n";
+
+        let edited = insert(manifest, "dependencies", "spdlog", "gh:gabime/spdlog@^1.14.1").unwrap();
+        assert_eq!(edited, "manifest_version = 1\n\n# rendering\n[dependencies]\nglfw = \"gh:glfw/glfw@^3.4\" # windowing\nspdlog = \"gh:gabime/spdlog@^1.14.1\"\n\n[dev-dependencies]\n");
+
+        let edited = insert(&edited, "test-dependencies", "doctest", "gh:doctest/doctest").unwrap();
+        assert!(edited.ends_with("[test-dependencies]\ndoctest = \"gh:doctest/doctest\"\n"), "{edited}");
+
+        assert!(matches!(insert(&edited, "dependencies", "glfw", "gh:glfw/glfw"), Err(SyncError::DependencyExists{ .. })));
+    }
+
+    #[test]
+    fn check_delete() {
+        let manifest = "manifest_version = 1\n\n[dependencies]\nglfw = \"gh:glfw/glfw@^3.4\" # windowing\nspdlog = \"gh:gabime/spdlog@^1.14.1\"\n\n[dev-dependencies]\nspdlog = { type = \"gh\", username = \"gabime\", repository = \"spdlog\" }\n";
+
+        let (edited, groups) = delete(manifest, "spdlog").unwrap();
+        assert_eq!(edited, "manifest_version = 1\n\n[dependencies]\nglfw = \"gh:glfw/glfw@^3.4\" # windowing\n\n[dev-dependencies]\n");
+        assert_eq!(groups, vec!["dependencies", "dev-dependencies"]);
+
+        assert!(delete(&edited, "spdlog").unwrap().1.is_empty());
+    }
+
+    #[test]
+    fn check_default_name() {
+        let name = |spec: &str| default_name(&ManifestDependency::parse(&Value::String(spec.to_string())).unwrap());
+        assert_eq!(name("gh:gabime/spdlog@^1.14"), "spdlog");
+        assert_eq!(name("git:git@gitlab.corp.example:tools/build-scripts.git"), "build-scripts");
+        assert_eq!(name("url:https://zlib.net/zlib-1.3.1.tar.gz"), "zlib");
+        assert_eq!(name("path:../libs/mycore/"), "mycore");
+    }
+}
@@ -0,0 +1,266 @@
+// git forges that can be written as a shorthand (gh:gabime/spdlog)
+//
+// the built in forges can be extended (or overridden) in the user's config file:
+//
+// [forges]
+// corp = "https://git.corp.example/{user}/{repo}.git"
+//
+// the same list is used to expand shorthands while parsing a manifest and to turn urls back into the ids
+// used by the cache, so a repository gets the same cache folder no matter how it was written.
+//
+// by default every protocol gets its own cache folders (https keeps the ids wares has always used). with
+// share_protocols = true at the top of the config file, a repository shares them whether it's reached over
+// https, ssh or git.
+
+// standard libraries
+use std::fs;
+use std::sync::OnceLock;
+
+// regex
+use regex::Regex;
+
+// serialization/deserialization
+use toml::{Table, Value};
+
+// internal dependencies
+use crate::utils;
+
+#[derive(Clone, Debug)]
+pub struct Forge {
+	// the shorthand used in manifests (also the start of the cache id)
+	pub name: String,
+	// other names for the shorthand (i.e. github for gh)
+	pub aliases: Vec<String>,
+	// the repository url, with {user} and {repo} in place of the username and repository
+	pub url_template: String,
+	// matches the locations (see repository_location) of urls made from url_template, capturing the username and repository
+	location_regex: Regex
+}
+
+impl Forge {
+	pub fn new(name: &str, aliases: &[&str], url_template: &str) -> Result<Forge, String> {
+		if !url_template.contains("{user}") || !url_template.contains("{repo}") {
+			return Err(format!("the url of the {name} forge has to contain {{user}} and {{repo}}, found {url_template}"));
+		}
+
+		let pattern = regex::escape(&repository_location(url_template))
+			.replace(r"\{user\}", r"([\w.-]+)")
+			.replace(r"\{repo\}", r"([\w.-]+)");
+		let location_regex = Regex::new(&format!("^{pattern}$")).map_err(|error| error.to_string())?;
+
+		Ok(Forge { name: name.to_string(), aliases: aliases.iter().map(|alias| alias.to_string()).collect(), url_template: url_template.to_string(), location_regex })
+	}
+
+	pub fn matches_name(&self, name: &str) -> bool {
+		self.name == name || self.aliases.iter().any(|alias| alias == name)
+	}
+
+	// the url of a repository on this forge
+	pub fn url(&self, user: &str, repository: &str) -> String {
+		self.url_template.replace("{user}", user).replace("{repo}", repository)
+	}
+
+	// the username and repository of a url on this forge, over any protocol
+	pub fn split_url(&self, url: &str) -> Option<(String, String)> {
+		let location = repository_location(url);
+		let captures = self.location_regex.captures(&location)?;
+
+		// the template might mention {repo} before {user}
+		let (user, repository) = if self.url_template.find("{user}") < self.url_template.find("{repo}") { (1, 2) } else { (2, 1) };
+		Some((captures.get(user)?.as_str().to_string(), captures.get(repository)?.as_str().to_string()))
+	}
+}
+
+fn builtin_forges() -> Vec<Forge> {
+	vec![
+		Forge::new("gh", &["github"], "https://github.com/{user}/{repo}.git").unwrap(),
+		Forge::new("gl", &["gitlab"], "https://gitlab.com/{user}/{repo}.git").unwrap(),
+		Forge::new("bb", &["bitbucket"], "https://bitbucket.org/{user}/{repo}.git").unwrap(),
+		Forge::new("cb", &["codeberg"], "https://codeberg.org/{user}/{repo}.git").unwrap(),
+		Forge::new("srht", &["sourcehut"], "https://git.sr.ht/~{user}/{repo}").unwrap()
+	]
+}
+
+// the dependency types wares handles itself, a forge with one of these names would take them over
+const BUILTIN_TYPES: [&str; 5] = ["git", "url", "zip", "archive", "path"];
+
+// reads the [forges] table of a config file, user forges replace the url of built in forges with the same name
+pub fn parse_forges(config: &str) -> Result<Vec<Forge>, String> {
+	let mut forges = builtin_forges();
+
+	let table: Table = config.parse().map_err(|error: toml::de::Error| error.to_string())?;
+	let Some(user_forges) = table.get("forges") else {
+		return Ok(forges);
+	};
+	let user_forges = user_forges.as_table().ok_or_else(|| String::from("forges has to be a table"))?;
+
+	for (name, url_template) in user_forges {
+		let Value::String(url_template) = url_template else {
+			return Err(format!("the url of the {name} forge has to be a string"));
+		};
+		if BUILTIN_TYPES.contains(&name.as_str()) {
+			return Err(format!("{name} can't be the name of a forge, it's a dependency type of its own ({})", BUILTIN_TYPES.join(", ")));
+		}
+
+		match forges.iter_mut().find(|existing| existing.matches_name(name)) {
+			Some(existing) => {
+				let aliases: Vec<&str> = existing.aliases.iter().map(String::as_str).collect();
+				*existing = Forge::new(&existing.name, &aliases, url_template)?;
+			},
+			None => forges.push(Forge::new(name, &[], url_template)?)
+		}
+	}
+
+	Ok(forges)
+}
+
+// reads the share_protocols setting of a config file (false if it isn't set)
+pub fn parse_share_protocols(config: &str) -> Result<bool, String> {
+	let table: Table = config.parse().map_err(|error: toml::de::Error| error.to_string())?;
+	match table.get("share_protocols") {
+		Some(value) => value.as_bool().ok_or_else(|| String::from("share_protocols has to be true or false")),
+		None => Ok(false)
+	}
+}
+
+// the settings of the user's config file
+struct Config {
+	forges: Vec<Forge>,
+	share_protocols: bool
+}
+
+// the user's config file, read once
+// an error describes what's wrong with the config file
+fn config() -> Result<&'static Config, &'static str> {
+	static CONFIG: OnceLock<Result<Config, String>> = OnceLock::new();
+	let config = CONFIG.get_or_init(|| {
+		let Some(config_file) = utils::config_file_fallback().filter(|file| file.exists()) else {
+			return Ok(Config { forges: builtin_forges(), share_protocols: false });
+		};
+
+		let contents = fs::read_to_string(&config_file).map_err(|error| format!("{:?}: {error}", config_file))?;
+		let forges = parse_forges(&contents).map_err(|error| format!("{:?}: {error}", config_file))?;
+		let share_protocols = parse_share_protocols(&contents).map_err(|error| format!("{:?}: {error}", config_file))?;
+		Ok(Config { forges, share_protocols })
+	});
+
+	config.as_ref().map_err(String::as_str)
+}
+
+// the built in forges plus the ones in the user's config file
+pub fn forges() -> Result<&'static [Forge], &'static str> {
+	config().map(|config| config.forges.as_slice())
+}
+
+// whether a repository shares its cache folders over every protocol (share_protocols in the config file)
+pub fn share_protocols() -> bool {
+	config().is_ok_and(|config| config.share_protocols)
+}
+
+// finds the forge for a shorthand (gh, github, bb, corp, ...)
+pub fn find(name: &str) -> Result<Option<&'static Forge>, &'static str> {
+	Ok(forges()?.iter().find(|forge| forge.matches_name(name)))
+}
+
+// the forge, username and repository of a url, if it was made by a known forge
+pub fn split_url(url: &str) -> Option<(&'static Forge, String, String)> {
+	forges().ok()?.iter().find_map(|forge| forge.split_url(url).map(|(user, repository)| (forge, user, repository)))
+}
+
+// the protocol a repository url is reached over (https, ssh, git, file, ...)
+pub fn protocol(url: &str) -> String {
+	match url.split_once("://") {
+		Some((scheme, _)) => scheme.to_lowercase(),
+		// scp-like ssh urls (git@github.com:gabime/spdlog.git)
+		None => match url.split_once(':') {
+			Some((host, _)) if !host.contains('/') => String::from("ssh"),
+			_ => String::from("file")
+		}
+	}
+}
+
+// the host and path of a repository url, without the scheme, username, port or .git suffix
+// i.e. https://github.com/gabime/spdlog.git, ssh://git@github.com:22/gabime/spdlog and git@github.com:gabime/spdlog.git
+// are all github.com/gabime/spdlog
+pub fn repository_location(url: &str) -> String {
+	let (host, path) = match url.split_once("://") {
+		// local repositories only have a path
+		Some(("file", path)) => ("", path),
+		Some((_, rest)) => rest.split_once('/').unwrap_or((rest, "")),
+		// scp-like ssh urls (git@github.com:gabime/spdlog.git)
+		None => match url.split_once(':') {
+			Some((host, path)) if !host.contains('/') => (host, path),
+			_ => ("", url)
+		}
+	};
+
+	// drop the username and port
+	let host = host.rsplit('@').next().unwrap_or(host);
+	let host = match host.strip_prefix('[') {
+		Some(ipv6) => ipv6.split(']').next().unwrap_or(ipv6),
+		None => host.split(':').next().unwrap_or(host)
+	};
+	let host = host.to_lowercase();
+	let host = host.strip_prefix("www.").unwrap_or(&host);
+
+	let path = path.trim_matches('/');
+	let path = path.strip_suffix(".git").unwrap_or(path).trim_end_matches('/');
+
+	if host.is_empty() {
+		path.to_string()
+	} else if path.is_empty() {
+		host.to_string()
+	} else {
+		format!("{host}/{path}")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_forge_urls() {
+        let forges = builtin_forges();
+        let bitbucket = forges.iter().find(|forge| forge.matches_name("bitbucket")).unwrap();
+        assert_eq!(bitbucket.url("atlassian", "python-bitbucket"), "https://bitbucket.org/atlassian/python-bitbucket.git");
+        assert_eq!(bitbucket.split_url("https://bitbucket.org/atlassian/python-bitbucket.git"), Some(("atlassian".to_string(), "python-bitbucket".to_string())));
+        assert_eq!(bitbucket.split_url("git@bitbucket.org:atlassian/python-bitbucket.git"), Some(("atlassian".to_string(), "python-bitbucket".to_string())));
+
+        let sourcehut = forges.iter().find(|forge| forge.matches_name("srht")).unwrap();
+        assert_eq!(sourcehut.split_url("https://git.sr.ht/~sircmpwn/scdoc"), Some(("sircmpwn".to_string(), "scdoc".to_string())));
+        assert_eq!(sourcehut.split_url("https://github.com/sircmpwn/scdoc.git"), None);
+    }
+
+    #[test]
+    fn check_user_forges() {
+        let forges = parse_forges("[forges]\ncorp = \"https://git.corp.example/{user}/{repo}.git\"\ngh = \"https://github.example/{user}/{repo}.git\"").unwrap();
+        let corp = forges.iter().find(|forge| forge.matches_name("corp")).unwrap();
+        assert_eq!(corp.split_url("ssh://git@git.corp.example:2222/tools/build.git"), Some(("tools".to_string(), "build".to_string())));
+
+        // overriding gh changes github too
+        assert_eq!(forges.iter().find(|forge| forge.matches_name("github")).unwrap().url("a", "b"), "https://github.example/a/b.git");
+
+        assert!(parse_forges("[forges]\ncorp = \"https://git.corp.example/tools.git\"").is_err());
+        assert!(parse_forges("[forges]\ngit = \"https://git.corp.example/{user}/{repo}.git\"").is_err());
+        assert!(parse_forges("[forges]\npath = \"https://git.corp.example/{user}/{repo}.git\"").is_err());
+    }
+
+    #[test]
+    fn check_repository_location() {
+        for url in ["https://github.com/gabime/spdlog.git", "http://www.github.com/gabime/spdlog", "ssh://git@github.com:22/gabime/spdlog.git", "git@github.com:gabime/spdlog.git", "git://github.com/gabime/spdlog.git/"] {
+            assert_eq!(repository_location(url), "github.com/gabime/spdlog", "{url}");
+        }
+        assert_eq!(repository_location("file:///srv/git/spdlog.git"), "srv/git/spdlog");
+
+        assert_eq!(protocol("git@github.com:gabime/spdlog.git"), "ssh");
+        assert_eq!(protocol("HTTPS://github.com/gabime/spdlog.git"), "https");
+    }
+
+    #[test]
+    fn check_share_protocols() {
+        assert_eq!(parse_share_protocols("[forges]\ncorp = \"https://git.corp.example/{user}/{repo}.git\"\n"), Ok(false));
+        assert_eq!(parse_share_protocols("share_protocols = true\n"), Ok(true));
+        assert!(parse_share_protocols("share_protocols = \"yes\"\n").is_err());
+    }
+}
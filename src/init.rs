@@ -0,0 +1,257 @@
+// sets up a project to use wares (wares init)
+//
+// writes a starter wares.toml, drops the get_wares script of the build system into the project and adds the
+// include and the sync call to the build script, in the order they have to run in. files that are already set
+// up are left alone, so running it twice doesn't change anything.
+
+// standard libraries
+use std::fs;
+use std::path::Path;
+
+// hashing
+use sha2::{Sha256, Digest};
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::utils;
+use crate::{SyncError, IoSnafu};
+
+// the build systems wares has modules for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BuildSystem {
+	CMake,
+	Premake
+}
+
+impl BuildSystem {
+	// the build system of a project, from the build script in its folder
+	pub fn detect(folder: &Path) -> Option<BuildSystem> {
+		if folder.join("CMakeLists.txt").exists() {
+			Some(BuildSystem::CMake)
+		} else if folder.join("premake5.lua").exists() {
+			Some(BuildSystem::Premake)
+		} else {
+			None
+		}
+	}
+
+	pub(crate) fn build_script(&self) -> &'static str {
+		match self {
+			BuildSystem::CMake => "CMakeLists.txt",
+			BuildSystem::Premake => "premake5.lua"
+		}
+	}
+
+	pub(crate) fn get_wares(&self) -> &'static str {
+		match self {
+			BuildSystem::CMake => "get_wares.cmake",
+			BuildSystem::Premake => "get_wares.lua"
+		}
+	}
+
+	// where get_wares installs the module, relative to itself
+	pub(crate) fn module_path(&self) -> &'static str {
+		match self {
+			BuildSystem::CMake => "wares/wares.cmake",
+			BuildSystem::Premake => "wares/wares.lua"
+		}
+	}
+
+	// the module get_wares installs, as it was published with this version of wares
+	pub(crate) fn module(&self) -> &'static str {
+		match self {
+			BuildSystem::CMake => include_str!("../cmake/wares.cmake"),
+			BuildSystem::Premake => include_str!("../premake/wares/wares.lua")
+		}
+	}
+}
+
+const MANIFEST: &str = "manifest_version = 1\n\n[dependencies]\n";
+
+// the get_wares script of this version of wares, the same one that's published with the release
+// (the error line is removed and the version and hash of the module are filled in)
+fn get_wares(build_system: BuildSystem) -> String {
+	let template = match build_system {
+		BuildSystem::CMake => include_str!("../cmake/get_wares.cmake"),
+		BuildSystem::Premake => include_str!("../premake/get_wares.lua")
+	};
+
+	template.split_inclusive('\n')
+		.filter(|line| !line.contains("Download this file directly from the releases on github!"))
+		.collect::<String>()
+		.replace("{{WARES_VERSION}}", &format!("\"{}\"", env!("CARGO_PKG_VERSION")))
+		.replace("{{WARES_HASH}}", &format!("\"{}\"", module_hash(build_system.module())))
+}
+
+// the sha256 of a module, as get_wares checks it
+pub(crate) fn module_hash(module: &str) -> String {
+	let digest: [u8; 32] = Sha256::digest(module.as_bytes()).into();
+	utils::format_hex(&digest)
+}
+
+// the index just past the closing parenthesis of the first call to a cmake command, if there is one
+fn end_of_call(script: &str, command: &str) -> Option<usize> {
+	let mut offset = 0;
+	for line in script.split_inclusive('\n') {
+		let trimmed = line.trim_start();
+		let is_call = trimmed.get(..command.len()).is_some_and(|start| start.eq_ignore_ascii_case(command))
+			&& trimmed[command.len()..].trim_start().starts_with('(');
+		if is_call {
+			let start = offset + line.len() - trimmed.len();
+			return script[start..].find(')').map(|end| start + end + 1);
+		}
+		offset += line.len();
+	}
+	None
+}
+
+// inserts text on the line after the given index
+fn insert_after(script: &mut String, index: usize, text: &str) {
+	match script[index..].find('\n') {
+		Some(end) => script.insert_str(index + end + 1, text),
+		None => {
+			script.push('\n');
+			script.push_str(text);
+		}
+	}
+}
+
+// adds include(get_wares.cmake) before project() (after cmake_minimum_required) and wares_sync() after it,
+// returns None if the script already includes get_wares.cmake
+pub fn patch_cmake(script: &str, project_name: &str) -> Option<String> {
+	if script.contains("get_wares.cmake") {
+		return None;
+	}
+
+	let mut script = script.to_string();
+	match end_of_call(&script, "cmake_minimum_required") {
+		Some(index) => insert_after(&mut script, index, "include(get_wares.cmake)\n"),
+		None => script.insert_str(0, "cmake_minimum_required(VERSION 3.19)\ninclude(get_wares.cmake)\n")
+	}
+
+	match end_of_call(&script, "project") {
+		Some(index) => insert_after(&mut script, index, "\nwares_sync()\n"),
+		None => {
+			// wares_sync() needs a project, so one is added right after the include
+			let include = "include(get_wares.cmake)";
+			let index = script.find(include).map(|index| index + include.len()).unwrap_or_default();
+			insert_after(&mut script, index, &format!("\nproject({project_name})\n\nwares_sync()\n"));
+		}
+	}
+
+	Some(script)
+}
+
+// adds the include of get_wares.lua and the sync to the top of a premake script (after its leading comments),
+// returns None if the script already includes get_wares.lua
+pub fn patch_premake(script: &str) -> Option<String> {
+	if script.contains("get_wares.lua") {
+		return None;
+	}
+
+	let header: usize = script.split_inclusive('\n')
+		.take_while(|line| line.trim_start().starts_with("--"))
+		.map(|line| line.len())
+		.sum();
+
+	let mut patched = script[..header].to_string();
+	if header > 0 {
+		if !patched.ends_with('\n') {
+			patched.push('\n');
+		}
+		patched.push('\n');
+	}
+	patched += "wares = include(\"get_wares.lua\")\n\nlocal deps = wares.sync()\n";
+	if header < script.len() {
+		patched.push('\n');
+	}
+	patched += script[header..].trim_start_matches('\n');
+
+	Some(patched)
+}
+
+// what init did to a file
+#[derive(Debug, PartialEq)]
+pub enum Change {
+	Created(String),
+	Patched(String),
+	Unchanged(String)
+}
+
+fn write_new(folder: &Path, name: &str, contents: &str) -> Result<Change, SyncError> {
+	let file = folder.join(name);
+	if file.exists() {
+		return Ok(Change::Unchanged(name.to_string()));
+	}
+
+	fs::write(&file, contents).context(IoSnafu{ context: format!("writing {:?}", file) })?;
+	Ok(Change::Created(name.to_string()))
+}
+
+// sets up the project in folder, with the build system's files if there is one
+pub fn init(folder: &Path, build_system: Option<BuildSystem>) -> Result<Vec<Change>, SyncError> {
+	fs::create_dir_all(folder).context(IoSnafu{ context: format!("creating {:?}", folder) })?;
+
+	let mut changes = vec![write_new(folder, "wares.toml", MANIFEST)?];
+
+	let Some(build_system) = build_system else {
+		return Ok(changes);
+	};
+
+	changes.push(write_new(folder, build_system.get_wares(), &get_wares(build_system))?);
+
+	let name = build_system.build_script();
+	let file = folder.join(name);
+	let exists = file.exists();
+	let script = if exists { fs::read_to_string(&file).context(IoSnafu{ context: format!("reading {:?}", file) })? } else { String::new() };
+
+	let project_name = crate::folder_of(&file)?.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or(String::from("App"));
+	let patched = match build_system {
+		BuildSystem::CMake => patch_cmake(&script, &project_name),
+		BuildSystem::Premake => patch_premake(&script)
+	};
+
+	match patched {
+		Some(patched) => {
+			fs::write(&file, patched).context(IoSnafu{ context: format!("writing {:?}", file) })?;
+			changes.push(if exists { Change::Patched(name.to_string()) } else { Change::Created(name.to_string()) });
+		},
+		None => changes.push(Change::Unchanged(name.to_string()))
+	}
+
+	Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_patch_cmake() {
+        let script = "cmake_minimum_required(VERSION 3.20)\nproject(App\n    LANGUAGES CXX)\n\nadd_executable(App src/main.cpp)\n";
+        let patched = patch_cmake(script, "App").unwrap();
+        assert_eq!(patched, "cmake_minimum_required(VERSION 3.20)\ninclude(get_wares.cmake)\nproject(App\n    LANGUAGES CXX)\n\nwares_sync()\n\nadd_executable(App src/main.cpp)\n");
+        assert!(patch_cmake(&patched, "App").is_none());
+
+        assert_eq!(patch_cmake("", "game").unwrap(), "cmake_minimum_required(VERSION 3.19)\ninclude(get_wares.cmake)\n\nproject(game)\n\nwares_sync()\n");
+    }
+
+    #[test]
+    fn check_patch_premake() {
+        let script = "-- premake5.lua\nworkspace \"Game\"\n";
+        let patched = patch_premake(script).unwrap();
+        assert_eq!(patched, "-- premake5.lua\n\nwares = include(\"get_wares.lua\")\n\nlocal deps = wares.sync()\n\nworkspace \"Game\"\n");
+        assert!(patch_premake(&patched).is_none());
+    }
+
+    #[test]
+    fn check_get_wares() {
+        for build_system in [BuildSystem::CMake, BuildSystem::Premake] {
+            let script = get_wares(build_system);
+            assert!(!script.contains("{{") && !script.contains("releases on github!"), "{script}");
+            assert!(script.contains(&format!("\"{}\"", env!("CARGO_PKG_VERSION"))));
+        }
+    }
+}
@@ -5,12 +5,15 @@
 use std::io::{self, BufReader};
 use std::io::BufWriter;
 use std::fs::{self, File};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::collections::BTreeMap;
 
 // terminal ui
 use colored::Colorize;
 
+// versioning
+use semver::Version;
+
 // error handling
 use snafu::{Snafu, ResultExt, Backtrace};
 
@@ -20,25 +23,36 @@ pub mod utils;
 pub mod cache;
 pub mod manifest;
 pub mod lock;
+pub mod resolver;
+pub mod progress;
+pub mod archive;
+pub mod forge;
+pub mod auth;
+pub mod patch;
+pub mod edit;
+pub mod tree;
+pub mod outdated;
+pub mod why;
+pub mod init;
+pub mod doctor;
+pub mod check;
 // -- private
 mod premake;
 
 // internal imports
 use manifest::{ManifestFile, ManifestFileParseError, LockingError};
-use lock::LockFile;
+use lock::{LockFile, LockedDependency};
+use resolver::{Resolver, Requirement, CacheSource, Conflict};
+use progress::Progress;
+
+// the version of wares this library was built from, exported so that `wares doctor` can find it in a built premake module
+#[no_mangle]
+pub static WARES_NATIVE_VERSION: &str = concat!("wares_native version ", env!("CARGO_PKG_VERSION"), "\0");
 
 // todo: convert paths to absolute
-// todo: add git submodule support
 // todo: contanerize code
-// todo: add spinners! and loading bars
-//        * spinners ==> installation
-//        * loading bars ==> ??
-// todo: add clean function
-// todo: don't rely on git for versioning information if we're offline -> fallback to cache
 // todo: deal with dependencies of multiple projects
-// todo: version resolver
 // todo: just write a build system?
-// todo: add support for zip: prefix
 // todo: integration with package manager
 // pretty-print errors
 
@@ -47,10 +61,12 @@ pub enum SyncError {
     #[snafu(display("IO Error: {source} when {context}"))]
     IoError{ source: io::Error, context: String, backtrace: Backtrace },
 
-    #[snafu(display("Failed to parse the manifest: {source}"))]
+    // the diagnostic is rendered when the error happens, while the file's contents are still around
+    #[snafu(display("Failed to parse the manifest:\n{diagnostic}"))]
     ManifestFileParseError{ 
 		#[snafu(backtrace)] // this should have an attached backtrace
-    	source: ManifestFileParseError 
+    	source: ManifestFileParseError,
+    	diagnostic: String
     },
 
     #[snafu(display("Failed to lock a dependency: {source}"))]
@@ -59,6 +75,63 @@ pub enum SyncError {
     	source: LockingError
     },
 
+    #[snafu(display("Failed to resolve the dependencies: {conflict}"))]
+    Resolution{ conflict: Box<Conflict> },
+
+    #[snafu(display("wares.lock already locks {name} to {existing}, which disagrees with {other}"))]
+    LockMerge{ name: String, existing: String, other: String },
+
+    #[snafu(display("{url} ({locked}) isn't in the cache and can't be downloaded while offline"))]
+    NotInstalledOffline{ url: String, locked: String },
+
+    #[snafu(display("{url} ({locked}) doesn't match wares.lock: expected the files in {folder} to hash to {expected}, but they hash to {found}\n  if this change is expected, run `wares sync --update-hashes` to record the new hash"))]
+    IntegrityMismatch{ url: String, locked: String, expected: String, found: String, folder: String },
+
+    #[snafu(display("Failed to download {url}: {source}"))]
+    Download{ url: String, source: Box<ureq::Error> },
+
+    #[snafu(display("{url} was expected to have the sha256 {expected}, but the download has the sha256 {found}"))]
+    ArchiveHashMismatch{ url: String, expected: String, found: String },
+
+    #[snafu(display("Failed to extract {archive}: {message}"))]
+    Extract{ archive: String, message: String },
+
+    #[snafu(display("The local dependency at {path} doesn't exist anymore"))]
+    MissingPath{ path: String },
+
+    #[snafu(display("{url} doesn't have a folder called {subdir} (its subdir)"))]
+    MissingSubdir{ url: String, subdir: String },
+
+    #[snafu(display("Failed to apply the patch {patch}: {message}"))]
+    Patch{ patch: String, message: String },
+
+    #[snafu(display("The patches of {url} changed since wares.lock was written, run `wares sync` again to lock them"))]
+    PatchesChanged{ url: String },
+
+    #[snafu(display("{name} isn't in wares.lock"))]
+    NotLocked{ name: String },
+
+    #[snafu(display("--precise needs exactly one package to update"))]
+    Precise,
+
+    #[snafu(display("There's no dependency called {name} in the manifest"))]
+    NotInManifest{ name: String },
+
+    #[snafu(display("{spec} isn't a valid dependency: {source}"))]
+    Spec{ spec: String, source: manifest::DependencyParseError },
+
+    #[snafu(display("Failed to edit the manifest: {source}"))]
+    ManifestEdit{ source: toml_edit::TomlError },
+
+    #[snafu(display("{value} isn't a duration, expected something like 30d or 12h"))]
+    InvalidDuration{ value: String },
+
+    #[snafu(display("{group} isn't a group of dependencies in wares.toml (a table called dependencies or ending in -dependencies)"))]
+    ManifestGroup{ group: String },
+
+    #[snafu(display("{group} already has a dependency called {name}, pick another name with --name"))]
+    DependencyExists{ group: String, name: String },
+
     #[snafu(display("Failed to serialize json: {source}"))]
     JsonError{ source: serde_json::Error, backtrace: Backtrace },
 
@@ -81,13 +154,26 @@ pub struct SyncRunner<'a> {
 	overrides: BTreeMap<String, String>,
 	// first time sync has been called by the configuring system?
 	first: bool,
+	// resolve and install from the cache only, without touching the network
+	offline: bool,
+	// how many dependencies are locked/installed at the same time
+	jobs: usize,
+	// replace the integrity hashes in the lock file instead of failing when they don't match
+	update_hashes: bool,
+	// the packages that are locked again from scratch (every package if it's empty), the others keep their
+	// previous lock as long as it still satisfies the manifest
+	relock: Option<Vec<String>>,
+	// the version the package being relocked has to be locked to
+	precise: Option<Version>,
+	// shows what's happening to each dependency
+	progress: Progress,
 
 	// store the manifest file
 	manifest: Option<ManifestFile>,
 }
 
 impl SyncRunner<'_> {
-	fn build<'a>(extra_deps: &'a Vec<String>, manifest_file: &'a Path, lock_file: &'a Path, cache_folder: &'a Path, force_update: bool, overrides: BTreeMap<String, String>, first: bool) -> SyncRunner<'a> {
+	pub fn build<'a>(extra_deps: &'a Vec<String>, manifest_file: &'a Path, lock_file: &'a Path, cache_folder: &'a Path, force_update: bool, overrides: BTreeMap<String, String>, first: bool) -> SyncRunner<'a> {
 		SyncRunner { extra_deps: extra_deps, 
 					 manifest_file: manifest_file, 
 					 lock_file: lock_file, 
@@ -95,16 +181,36 @@ impl SyncRunner<'_> {
 					 update: force_update, 
 					 overrides: overrides, 
 					 first: first,
+					 offline: utils::offline_fallback(),
+					 jobs: utils::jobs_fallback(),
+					 update_hashes: false,
+					 relock: None,
+					 precise: None,
+					 progress: Progress::new(),
 					 manifest: None }
 	}
 
-	fn read_manifest(&mut self) -> Result<(), SyncError>{
-		// try to read the manifest file
-		let manifest_file_contents = fs::read_to_string(self.manifest_file).context(IoSnafu{ context: format!("reading file \"{:?}\"", self.manifest_file) })?;
-		
-		// serialize the manifest
-		self.manifest = Some(ManifestFile::parse(&manifest_file_contents).context(ManifestFileParseSnafu)?);
+	pub fn set_offline(&mut self, offline: bool) {
+		self.offline = offline;
+	}
+
+	pub fn set_jobs(&mut self, jobs: usize) {
+		self.jobs = jobs.max(1);
+	}
+
+	pub fn set_update_hashes(&mut self, update_hashes: bool) {
+		self.update_hashes = update_hashes;
+	}
+
+	// locks the packages again (all of them if packages is empty), optionally to an exact version
+	pub fn set_relock(&mut self, packages: Vec<String>, precise: Option<Version>) {
+		self.update = true;
+		self.relock = Some(packages);
+		self.precise = precise;
+	}
 
+	fn read_manifest(&mut self) -> Result<(), SyncError>{
+		self.manifest = Some(read_manifest(self.manifest_file)?);
 		Ok(())
 	}
 
@@ -124,21 +230,88 @@ impl SyncRunner<'_> {
 		let mut dep_groups = vec![&default_group];
 		dep_groups.extend(self.extra_deps.iter());
 
+		// every dependency in our own manifest is a root requirement
+		let mut roots: Vec<Requirement> = Vec::new();
 		for dep_group in dep_groups {
-			for dep in &manifest.dependencies[dep_group] {
-				lockfile.dependencies.insert(dep.name.clone(), dep.lock().context(LockSnafu)?);
+			roots.extend(manifest.group(dep_group)?.iter().map(|dep| Requirement::root(dep_group, dep.clone())));
+		}
+
+		// other projects sharing the lock file have already made their choices during this run
+		let parent_lockfile: Option<LockFile> = if self.first {
+			None
+		} else {
+			Some(self.read_lockfile()?)
+		};
+		let mut pinned = parent_lockfile.as_ref().map(|parent| parent.dependencies.clone()).unwrap_or_default();
+		if self.update_hashes {
+			for dependency in pinned.values_mut() {
+				dependency.integrity = None;
+			}
+		}
+
+		// the last lock is kept where it still fits, except for the packages that are being updated
+		let previous_lockfile: Option<LockFile> = match &parent_lockfile {
+			Some(_) => None,
+			None => self.read_lockfile().ok()
+		};
+		let mut preferred = previous_lockfile.as_ref().map(|previous| previous.dependencies.clone()).unwrap_or_default();
+		match &self.relock {
+			Some(packages) if packages.is_empty() => preferred.clear(),
+			Some(packages) => {
+				for name in packages {
+					if previous_lockfile.is_some() && preferred.remove(name).is_none() {
+						return NotLockedSnafu{ name: name.clone() }.fail();
+					}
+				}
+			},
+			None => {}
+		}
+
+		let mut source = CacheSource::new(self.cache_folder, &self.overrides, self.offline, self.jobs, &self.progress);
+		let mut resolver = Resolver::new(&mut source, pinned);
+		resolver.set_preferred(preferred);
+		if let Some(version) = &self.precise {
+			match self.relock.as_deref() {
+				Some([name]) => resolver.set_precise(name, version.clone()),
+				_ => return PreciseSnafu.fail()
+			}
+		}
+		let resolution = resolver.resolve(roots);
+		self.progress.finish();
+		lockfile.dependencies = resolution?;
+
+		if let Some(previous) = &previous_lockfile {
+			print_changes(previous, &lockfile);
+		}
+
+		// a commit's files never change, so the hashes recorded by the last lock are kept (and checked during the install)
+		if let (Some(previous), false) = (parent_lockfile.as_ref().or(previous_lockfile.as_ref()), self.update_hashes) {
+			for (name, dependency) in lockfile.dependencies.iter_mut() {
+				match previous.dependencies.get(name) {
+					Some(locked) if dependency.integrity.is_none() && locked.same_source(dependency) => {
+						dependency.integrity = locked.integrity.clone();
+						dependency.submodules = locked.submodules.clone();
+					},
+					_ => {}
+				}
 			}
 		}
 
+		// every chosen commit is installed by now, so record what its files hash to
+		self.record_hashes(&mut lockfile.dependencies)?;
+		self.progress.finish();
+
 		// write the lock file
-		if self.first {
-			println!("{} {}", "Writing".green(), "wares.lock".yellow());
-			serde_json::to_writer(BufWriter::new(File::create(self.lock_file).context(IoSnafu{ context: format!("creating \"{:?}\"", self.lock_file) })?), &lockfile).context(JsonSnafu)?;
-		} else {
-			println!("{} {}", "Merging".cyan(), "wares.lock".yellow());
-			let mut parent_lockfile: LockFile = serde_json::from_reader(BufReader::new(File::open(self.lock_file).context(IoSnafu{ context: format!("opening {:?}", self.lock_file) })?)).context(JsonSnafu)?;
-			parent_lockfile.merge(&lockfile);
-			serde_json::to_writer(BufWriter::new(File::create(self.lock_file).context(IoSnafu{ context: format!("creating \"{:?}\"", self.lock_file) })?), &parent_lockfile).context(JsonSnafu)?;
+		match parent_lockfile {
+			None => {
+				println!("{} {}", "Writing".green(), "wares.lock".yellow());
+				self.write_lockfile(&lockfile)?;
+			},
+			Some(mut parent_lockfile) => {
+				println!("{} {}", "Merging".cyan(), "wares.lock".yellow());
+				parent_lockfile.merge(&lockfile)?;
+				self.write_lockfile(&parent_lockfile)?;
+			}
 		}
 
 		Ok(lockfile)
@@ -156,28 +329,102 @@ impl SyncRunner<'_> {
 		let default_group = String::from("dependencies");
 		let mut dep_groups = vec![&default_group];
 		dep_groups.extend(self.extra_deps.iter());
-		let my_dependencies: Vec<&str> = manifest.dep_names(&dep_groups);
+		let my_dependencies: Vec<&str> = manifest.dep_names(&dep_groups)?;
+
+		// our own dependencies plus everything they depend on
+		let required_dependencies: Vec<String> = lockfile.closure(&my_dependencies);
 
 		let mut installation_info: BTreeMap<String, String> = BTreeMap::new();
 
 		// add all of the overrides that apply to our folder to the installation_info
 		for (name, folder) in &self.overrides {
-			if my_dependencies.contains(&name.as_str()) {
+			if required_dependencies.contains(name) {
 				// the folder location should be absolute to the root wares path so that overrides actually function correctly
 				let full_path = utils::get_full_path(folder).context(IoSnafu{ context: format!("grabbing full path of {folder}") })?;
 				installation_info.insert(name.clone(), full_path.to_str().expect("Path contains invalid Unicode characters").to_string());
 			}
 		}
 		
-		for (name, dependency) in lockfile.dependencies {
-			if !installation_info.contains_key(&name) && my_dependencies.contains(&name.as_str()) {
-				installation_info.insert(name, dependency.install(self.cache_folder)?);
+		let mut to_install: Vec<(String, LockedDependency)> = lockfile.dependencies.into_iter()
+			.filter(|(name, _)| !installation_info.contains_key(name) && required_dependencies.contains(name))
+			.collect();
+
+		// the recorded hashes are thrown away and recorded again once everything is installed
+		if self.update_hashes {
+			for (_, dependency) in to_install.iter_mut() {
+				dependency.integrity = None;
 			}
 		}
 
+		let installed = utils::parallel_map(&to_install, self.jobs, |(name, dependency)| dependency.install(self.cache_folder, self.offline, self.progress.line(name)));
+		self.progress.finish();
+
+		let mut updated: BTreeMap<String, LockedDependency> = BTreeMap::new();
+		for ((name, dependency), folder) in to_install.into_iter().zip(installed) {
+			installation_info.insert(name.clone(), folder?);
+			updated.insert(name, dependency);
+		}
+
+		if self.update_hashes {
+			self.record_hashes(&mut updated)?;
+			self.write_hashes(&updated)?;
+		}
+
 		Ok(installation_info)
 	}
 
+	// hashes the installed files of every dependency locked to a commit that doesn't have a hash yet
+	// (and records the commits of their submodules)
+	fn record_hashes(&self, dependencies: &mut BTreeMap<String, LockedDependency>) -> Result<(), SyncError> {
+		let unhashed: Vec<(String, LockedDependency)> = dependencies.iter()
+			.filter(|(name, dependency)| dependency.has_fixed_contents() && dependency.integrity.is_none() && !self.overrides.contains_key(*name))
+			.map(|(name, dependency)| (name.clone(), dependency.clone()))
+			.collect();
+
+		let hashes = utils::parallel_map(&unhashed, self.jobs, |(name, dependency)| {
+			let folder = dependency.install_root(self.cache_folder, self.offline, self.progress.line(name))?;
+			let submodules = match dependency.submodules {
+				Some(_) => Some(lock::submodule_commits(Path::new(&folder), dependency.sparse.as_deref())?),
+				None => None
+			};
+			Ok((cache::content_hash(Path::new(&folder))?, submodules))
+		});
+
+		for ((name, _), hash) in unhashed.iter().zip(hashes) {
+			let (hash, submodules) = hash?;
+			let dependency = dependencies.get_mut(name).expect("only existing dependencies are hashed");
+			dependency.integrity = Some(hash);
+			dependency.submodules = submodules;
+		}
+
+		Ok(())
+	}
+
+	// replaces the hashes of the given dependencies in the lock file on the disk
+	fn write_hashes(&self, dependencies: &BTreeMap<String, LockedDependency>) -> Result<(), SyncError> {
+		let mut lockfile = self.read_lockfile()?;
+
+		for (name, dependency) in dependencies {
+			if let Some(locked) = lockfile.dependencies.get_mut(name) {
+				if locked.same_source(dependency) && locked.integrity != dependency.integrity {
+					println!("{} the hash of {} to {}", "Updating".cyan(), name.green(), dependency.integrity.as_deref().unwrap_or("nothing"));
+					locked.integrity = dependency.integrity.clone();
+					locked.submodules = dependency.submodules.clone();
+				}
+			}
+		}
+
+		self.write_lockfile(&lockfile)
+	}
+
+	fn read_lockfile(&self) -> Result<LockFile, SyncError> {
+		read_lockfile(self.lock_file)
+	}
+
+	fn write_lockfile(&self, lockfile: &LockFile) -> Result<(), SyncError> {
+		write_lockfile(self.lock_file, lockfile)
+	}
+
 	pub fn needs_update(&self) -> bool {
 		self.update || 
 			!self.lock_file.exists() || 
@@ -193,9 +440,78 @@ impl SyncRunner<'_> {
 		let lockfile = if self.needs_update() {
 			self.update()?
 		} else {
-			serde_json::from_reader(BufReader::new(File::open(self.lock_file).context(IoSnafu{context: format!("opening \"{:?}\"", self.lock_file)})?)).context(JsonSnafu)? // else read from the lock file
+			self.read_lockfile()? // else read from the lock file
 		};
 
 		self.install(lockfile)
 	}
-}
\ No newline at end of file
+}
+
+// reads a manifest file, with the paths in it made absolute
+pub fn read_manifest(manifest_file: &Path) -> Result<ManifestFile, SyncError> {
+	// try to read the manifest file
+	let manifest_file_contents = fs::read_to_string(manifest_file).context(IoSnafu{ context: format!("reading file \"{:?}\"", manifest_file) })?;
+
+	// serialize the manifest
+	let mut manifest = parse_manifest(manifest_file, &manifest_file_contents)?;
+	manifest.resolve_paths(&folder_of(manifest_file)?);
+	Ok(manifest)
+}
+
+// parses the contents of a manifest file, errors point at the line they're on
+pub(crate) fn parse_manifest(manifest_file: &Path, contents: &str) -> Result<ManifestFile, SyncError> {
+	ManifestFile::parse(contents).with_context(|error| ManifestFileParseSnafu{ diagnostic: error.render(&manifest_file.display().to_string(), contents) })
+}
+
+// reads a lock file, with the paths in it made absolute again
+pub fn read_lockfile(lock_file: &Path) -> Result<LockFile, SyncError> {
+	let mut lockfile: LockFile = serde_json::from_reader(BufReader::new(File::open(lock_file).context(IoSnafu{ context: format!("opening {:?}", lock_file) })?)).context(JsonSnafu)?;
+	lockfile.absolute_paths(&folder_of(lock_file)?);
+	Ok(lockfile)
+}
+
+// writes a lock file, with the paths in it relative to its folder
+pub(crate) fn write_lockfile(lock_file: &Path, lockfile: &LockFile) -> Result<(), SyncError> {
+	let mut lockfile = LockFile{ lockfile_version: lockfile.lockfile_version, dependencies: lockfile.dependencies.clone() };
+	lockfile.relative_paths(&folder_of(lock_file)?);
+	serde_json::to_writer(BufWriter::new(File::create(lock_file).context(IoSnafu{ context: format!("creating \"{:?}\"", lock_file) })?), &lockfile).context(JsonSnafu)
+}
+
+// shows what locking again changed
+fn print_changes(previous: &LockFile, lockfile: &LockFile) {
+	for (name, dependency) in &lockfile.dependencies {
+		match previous.dependencies.get(name) {
+			Some(locked) if locked.same_source(dependency) => {},
+			Some(locked) => println!("{} {} {} -> {}", "Updating".cyan(), name.green(), locked, dependency),
+			None => println!("{} {} {}", "Adding".cyan(), name.green(), dependency)
+		}
+	}
+
+	for (name, locked) in &previous.dependencies {
+		if !lockfile.dependencies.contains_key(name) {
+			println!("{} {} {}", "Removing".cyan(), name.green(), locked);
+		}
+	}
+}
+
+// reads the wares.toml file inside of a dependency's folder, if it has one
+fn read_nested_manifest(folder: &Path) -> Result<Option<ManifestFile>, SyncError> {
+	let manifest_file = folder.join("wares.toml");
+	if !manifest_file.exists() {
+		return Ok(None);
+	}
+
+	let manifest_file_contents = fs::read_to_string(&manifest_file).context(IoSnafu{ context: format!("reading file \"{:?}\"", manifest_file) })?;
+	let mut manifest = parse_manifest(&manifest_file, &manifest_file_contents)?;
+	manifest.resolve_paths(&utils::get_full_path(folder).context(IoSnafu{ context: format!("grabbing full path of {:?}", folder) })?);
+	Ok(Some(manifest))
+}
+
+// the absolute path of the folder a file is in
+pub(crate) fn folder_of(file: &Path) -> Result<PathBuf, SyncError> {
+	let folder = match file.parent() {
+		Some(parent) if !parent.as_os_str().is_empty() => parent,
+		_ => Path::new(".")
+	};
+	utils::get_full_path(folder).context(IoSnafu{ context: format!("grabbing full path of {:?}", folder) })
+}
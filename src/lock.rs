@@ -1,69 +1,146 @@
 // standard libraries
-use std::sync::OnceLock;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::fs;
 use std::collections::BTreeMap;
 use std::fmt;
 
 // git2
 use git2::Repository;
-use git2::build::RepoBuilder;
+use git2::build::{CheckoutBuilder, RepoBuilder};
 
-// regex
-use regex::Regex;
+// versioning
+use semver::Version;
+
+// hashing
+use sha2::{Sha256, Digest};
 
 // serialization/deserialization
 use serde::{Serialize, Deserialize};
 use serde::ser::{Serializer, SerializeMap};
 use serde::de::{Deserializer, Visitor, Error};
 
-// terminal ui
-use spinoff::{Spinner, spinners, Color};
-
 // error handling
 use snafu::ResultExt;
 
 // internal dependencies
 use crate::utils;
-use crate::{SyncError, IoSnafu, GitSnafu};
-
-#[derive(Clone, Debug)]
+use crate::forge;
+use crate::auth;
+use crate::progress::Line;
+use crate::archive::{self, ArchiveFormat};
+use crate::patch::{self, PatchSet};
+use crate::cache::{self, CacheIndex, CacheLock, CachedInstall, CachedObject};
+use crate::{SyncError, IoSnafu, GitSnafu, LockMergeSnafu, NotInstalledOfflineSnafu, IntegrityMismatchSnafu, MissingPathSnafu, MissingSubdirSnafu, PatchesChangedSnafu};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum LockedDependencyId {
 	MainBranch, // no value provided besides the url by the user
 	Branch(String), // a branch provided by the user
-	Oid(git2::Oid) // a commit hash, rev, tag, or version requirement provided by the user
+	Oid(git2::Oid), // a commit hash, rev, tag, or version requirement provided by the user
+	Archive{ sha256: String, strip_components: usize }, // an archive, identified by the sha256 of the file
+	Path // a folder on the disk, used where it is instead of being copied into the cache
 }
 
 #[derive(Clone, Debug)]
 pub struct LockedDependency {
 	url: String, // git/github/gitlab url associated with this dependency
 	id: LockedDependencyId, // commit/version (aka tag)/revision/branch associated with this dependency
+	pub version: Option<Version>, // the version the oid was tagged with, if it was chosen by a version requirement
+	pub reference: Option<String>, // the remote ref (tag, branch, ...) the oid was resolved from
+	pub dependencies: Vec<String>, // names of the dependencies declared by this dependency's own wares.toml
+	pub integrity: Option<String>, // hash of the checked out files (see cache::content_hash), only recorded for commits
+	pub submodules: Option<BTreeMap<String, String>>, // the commit of every submodule by path, if submodules are checked out (only recorded for commits)
+	pub patches: Option<PatchSet>, // patches applied after the checkout
+	pub subdir: Option<String>, // the folder inside of the checkout that holds the package
+	pub sparse: Option<Vec<String>>, // the only paths that are checked out (relative to the repository), everything is checked out if None
+}
+
+// turns a repository url into the id used for it in the cache (i.e. gh-gabime-spdlog, see forge.rs for the prefixes)
+pub fn package_id(url: &str) -> String {
+	id_of(url, forge::share_protocols())
+}
+
+// repositories that aren't reached over https get the protocol in front of their id (ssh-gh-gabime-spdlog),
+// unless they share their checkouts with every protocol
+pub(crate) fn id_of(url: &str, share_protocols: bool) -> String {
+	if Path::new(url).is_absolute() {
+		format!("path-{}", utils::sanitize_filename(url))
+	} else if ArchiveFormat::from_url(url).is_some() {
+		// release tarballs of different projects are often called the same (v1.0.tar.gz), the url tells them apart
+		format!("archive-{}-{}", archive::archive_name(url), archive::url_hash(url))
+	} else {
+		let id = match forge::split_url(url) {
+			Some((forge, user, repository)) => format!("{}-{}-{}", forge.name, user, repository),
+			None => utils::sanitize_filename(forge::repository_location(url))
+		};
+
+		match forge::protocol(url) {
+			protocol if share_protocols || protocol == "https" => id,
+			protocol => format!("{protocol}-{id}")
+		}
+	}
 }
 
 impl LockedDependency {
 	pub fn new(url: String, id: LockedDependencyId) -> Self {
-		LockedDependency { url: url, id: id }
+		LockedDependency { url: url, id: id, version: None, reference: None, dependencies: Vec::new(), integrity: None, submodules: None, patches: None, subdir: None, sparse: None }
+	}
+
+	pub fn url(&self) -> &str {
+		&self.url
 	}
 
-	fn uuid(&self) -> String {
-		static GITHUB_REGEX: OnceLock<Regex> = OnceLock::new();
-		let github_regex = GITHUB_REGEX.get_or_init(|| { Regex::new(r"https://github\.com/([A-Za-z0-9_.-]*)/([A-Za-z0-9_.-]*).git").unwrap() });
+	// the commit this is locked to, if it's locked to one
+	pub fn oid(&self) -> Option<git2::Oid> {
+		match self.id {
+			LockedDependencyId::Oid(oid) => Some(oid),
+			_ => None
+		}
+	}
 
-		static GITLAB_REGEX: OnceLock<Regex> = OnceLock::new();
-		let gitlab_regex = GITLAB_REGEX.get_or_init(|| { Regex::new(r"https://gitlab\.com/([A-Za-z0-9_.-]*)/([A-Za-z0-9_.-]*).git").unwrap() });
+	// true if both lock the same commit/branch of the same repository (checked out the same way)
+	pub fn same_source(&self, other: &LockedDependency) -> bool {
+		self.url == other.url && self.id == other.id && self.submodules.is_some() == other.submodules.is_some()
+			&& self.patches.as_ref().map(|patches| &patches.sha256) == other.patches.as_ref().map(|patches| &patches.sha256)
+			&& self.subdir == other.subdir && self.sparse == other.sparse
+	}
 
-		static URL_REGEX: OnceLock<Regex> = OnceLock::new();
-		let url_regex = URL_REGEX.get_or_init(|| { Regex::new(r"(?:https://)?(?:www\.)?([A-Za-z0-9_.-/]*).git").unwrap() });
+	// true if this is locked to files that never change (a commit or an archive, rather than a branch that moves)
+	pub fn has_fixed_contents(&self) -> bool {
+		matches!(self.id, LockedDependencyId::Oid(_) | LockedDependencyId::Archive{ .. })
+	}
 
-		let start = if let Some(github) = github_regex.captures(&self.url) {
-			format!("gh-{}-{}", &github[1], &github[2])
-		} else if let Some(gitlab) = gitlab_regex.captures(&self.url) {
-			format!("gl-{}-{}", &gitlab[1], &gitlab[2])
-		} else if let Some(url) = url_regex.captures(&self.url) {
-			utils::sanitize_filename(&url[1])
-		} else {
-			utils::sanitize_filename(&self.url)
-		};
-		
+	// true if this is a local folder rather than something that's installed into the cache
+	pub fn is_path(&self) -> bool {
+		self.id == LockedDependencyId::Path
+	}
+
+	// identifies the repository in the cache, shared by every checkout of it
+	pub fn package_id(&self) -> String {
+		package_id(&self.url)
+	}
+
+	// identifies this checkout in the cache (the name of its folder)
+	pub fn uuid(&self) -> String {
+		let start = self.package_id();
+		let mut uuid = self.source_uuid(start);
+
+		// a checkout with its submodules (or patches, or only some of its files) has different files than one without
+		// the subdir isn't part of it, it only picks a folder out of the same files
+		if self.submodules.is_some() {
+			uuid += "-submodules";
+		}
+		if let Some(sparse) = &self.sparse {
+			let digest: [u8; 32] = Sha256::digest(sparse.join("\n").as_bytes()).into();
+			uuid += &format!("-sparse-{:.16}", utils::format_hex(&digest));
+		}
+		if let Some(patches) = &self.patches {
+			uuid += &format!("-patched-{:.16}", patches.sha256);
+		}
+		uuid
+	}
+
+	fn source_uuid(&self, start: String) -> String {
 		match &self.id {
 			LockedDependencyId::MainBranch => {
 				format!("{}-latest", start)
@@ -73,65 +150,412 @@ impl LockedDependency {
 			},
 			LockedDependencyId::Oid(oid) => {
 				format!("{}-{}", start, oid)
-			}
+			},
+			LockedDependencyId::Archive{ sha256, strip_components: 0 } => {
+				format!("{}-{:.16}", start, sha256)
+			},
+			LockedDependencyId::Archive{ sha256, strip_components } => {
+				format!("{}-{:.16}-strip{}", start, sha256, strip_components)
+			},
+			LockedDependencyId::Path => start
 		}
 	}
 
-	// installs the github repository into the cache specified at path 
-	// returns the installation folder as a string
-	pub fn install(&self, cache_path: &Path) -> Result<String, SyncError> {
-		let mut install_path = utils::get_full_path(cache_path).context(IoSnafu{ context: format!("grabbing full path of {:?}", cache_path) })?; // might? error out if the cache doesn't exist yet
-		install_path.push(self.uuid()); // this path should now be absolute
-
-		if !install_path.exists() {
-			let mut spinner = Spinner::new(spinners::Dots, format!("Installing {} to {}", self.url, install_path.display()), Color::Blue);
-
-			match self.id {
-				LockedDependencyId::Oid(oid) => {
-					// git doesn't allow cloning a commit directly, so instead we:
-					// (1) initalize an empty repository
-					let repository = Repository::init(&install_path).context(GitSnafu)?;
-					
-					// (2) add the remote url as the origin
-					let mut origin = repository.remote("origin", &self.url).context(GitSnafu)?;
-					
-					// (3) fetch the specific revision
-					origin.fetch(&[oid.to_string()], Some(git2::FetchOptions::new().depth(1)), None).context(GitSnafu)?;
-					
-					// (4) reset the branch to the revision of interest
-					repository.reset(&repository.find_object(oid, None).context(GitSnafu)?, git2::ResetType::Hard, None).context(GitSnafu)?;  
+	// what the cache index records this dependency as
+	pub fn cached_object(&self) -> CachedObject {
+		match &self.id {
+			LockedDependencyId::MainBranch | LockedDependencyId::Path => CachedObject::Latest,
+			LockedDependencyId::Branch(branch) => CachedObject::Branch(branch.clone()),
+			LockedDependencyId::Oid(oid) => {
+				let hash: [u8; 20] = oid.as_bytes().try_into().expect("git2::Oid is always 20 bytes");
+				match (&self.version, &self.reference) {
+					(Some(version), _) => CachedObject::Version{ semver: version.clone(), hash: hash },
+					(None, Some(reference)) => match reference.strip_prefix("refs/tags/") {
+						Some(tag) => CachedObject::Tag{ tag: tag.to_string(), hash: hash },
+						None => CachedObject::Rev{ rev: reference.clone(), hash: hash }
+					},
+					(None, None) => CachedObject::Commit(hash)
+				}
+			},
+			LockedDependencyId::Archive{ sha256, .. } => CachedObject::Archive(utils::parse_hex::<32>(sha256).expect("archive hashes are checked when they're read"))
+		}
+	}
+
+	// only the locked commit is fetched, except from local repositories (libgit2 can't fetch shallow from them)
+	fn fetch_options(&self) -> git2::FetchOptions<'static> {
+		let mut fetch_options = auth::fetch_options();
+		if !self.url.starts_with("file://") && !Path::new(&self.url).exists() {
+			fetch_options.depth(1);
+		}
+		fetch_options
+	}
+
+	// the paths written to the disk for a sparse checkout, the package's own wares.toml is always included so
+	// its dependencies can be read (and .gitmodules, which git needs to find the submodules)
+	fn checkout_paths(&self) -> Option<Vec<String>> {
+		let mut paths = self.sparse.clone()?;
+		paths.push(String::from(".gitmodules"));
+		paths.push(match &self.subdir {
+			Some(subdir) => format!("{subdir}/wares.toml"),
+			None => String::from("wares.toml")
+		});
+		Some(paths)
+	}
+
+	// checks that the subdir exists in a checkout
+	fn check_subdir(&self, root: &Path) -> Result<(), SyncError> {
+		match &self.subdir {
+			Some(subdir) if !root.join(subdir).is_dir() => MissingSubdirSnafu{ url: self.url.clone(), subdir: subdir.clone() }.fail(),
+			_ => Ok(())
+		}
+	}
+
+	// clones the locked commit/branch (or extracts the archive) into an empty folder
+	fn checkout(&self, path: &Path) -> Result<(), SyncError> {
+		let paths = self.checkout_paths();
+		// libgit2 can't clone only part of a repository, but it can leave everything else out of the worktree
+		let checkout_builder = || {
+			let mut checkout_builder = CheckoutBuilder::new();
+			for path in paths.iter().flatten() {
+				checkout_builder.path(path);
+			}
+			checkout_builder
+		};
+
+		match &self.id {
+			LockedDependencyId::Archive{ sha256, strip_components } => {
+				let format = ArchiveFormat::from_url(&self.url).ok_or_else(|| SyncError::Extract{ archive: self.url.clone(), message: String::from("unknown archive format") })?;
+				let archive_path = archive::fetch(&self.url, sha256)?;
+				archive::extract(&archive_path, format, path, *strip_components, paths.as_deref())?;
+
+				// the download isn't needed once it's in the cache
+				let _ = fs::remove_file(&archive_path);
+			},
+			&LockedDependencyId::Oid(oid) => {
+				// git doesn't allow cloning a commit directly, so instead we:
+				// (1) initalize an empty repository
+				let repository = Repository::init(path).context(GitSnafu)?;
+				
+				// (2) add the remote url as the origin
+				let mut origin = repository.remote("origin", &self.url).context(GitSnafu)?;
+				
+				// (3) fetch the specific revision
+				origin.fetch(&[oid.to_string()], Some(&mut self.fetch_options()), None).context(GitSnafu)?;
+				
+				// (4) reset the branch to the revision of interest
+				repository.reset(&repository.find_object(oid, None).context(GitSnafu)?, git2::ResetType::Hard, Some(&mut checkout_builder())).context(GitSnafu)?;  
+
+				if self.submodules.is_some() {
+					checkout_submodules(&repository, paths.as_deref())?;
 				}
-				_ => {
-					// main branch or specific one
-					// todo: add branch update
-					let mut clone_builder = RepoBuilder::new();
-					let mut fetch_options = git2::FetchOptions::new();
-					fetch_options.depth(1);
-					clone_builder.fetch_options(fetch_options);
-
-					if let LockedDependencyId::Branch(branch) = &self.id {
-						clone_builder.branch(branch);
-					}
 
-					clone_builder.clone(&self.url, &install_path).context(GitSnafu)?;
+				// (5) remember the ref the commit was resolved from, so that it can be resolved again while offline
+				if let Some(reference) = &self.reference {
+					let local_reference = match reference.strip_prefix("refs/heads/") {
+						Some(branch) => format!("refs/remotes/origin/{branch}"),
+						None => reference.clone()
+					};
+					repository.reference(&local_reference, oid, true, "wares: resolved ref").context(GitSnafu)?;
 				}
 			}
+			_ => {
+				// main branch or specific one
+				// todo: add branch update
+				let mut clone_builder = RepoBuilder::new();
+				clone_builder.fetch_options(self.fetch_options());
+				clone_builder.with_checkout(checkout_builder());
+
+				if let LockedDependencyId::Branch(branch) = &self.id {
+					clone_builder.branch(branch);
+				}
+
+				let repository = clone_builder.clone(&self.url, path).context(GitSnafu)?;
+				if self.submodules.is_some() {
+					checkout_submodules(&repository, paths.as_deref())?;
+				}
+			}
+		}
+
+		self.check_subdir(path)
+	}
+
+	// the commit checked out at path (or the sha256 of the extracted archive), if path holds a usable install of this dependency
+	fn installed_version(&self, path: &Path) -> Option<String> {
+		// archives are only moved into the cache once they're completely extracted
+		if let LockedDependencyId::Archive{ sha256, .. } = &self.id {
+			return path.is_dir().then(|| sha256.clone());
+		}
+
+		let repository = Repository::open(path).ok()?;
+		let commit = repository.head().ok()?.peel_to_commit().ok()?.id();
+
+		match self.id {
+			LockedDependencyId::Oid(oid) if oid != commit => None,
+			_ => Some(commit.to_string())
+		}
+	}
+
+	// the commit (or archive hash) installed in the cache, None if it isn't installed or the checkout is at another commit
+	pub fn installed_commit(&self, cache_path: &Path) -> Option<String> {
+		let cache_path = utils::get_full_path(cache_path).ok()?;
+		self.installed_version(&cache_path.join(self.uuid()))
+	}
+
+	// checks the files at path against the integrity hash recorded in the lock file (if there is one)
+	fn verify(&self, path: &Path, install_path: &Path) -> Result<(), SyncError> {
+		let Some(expected) = &self.integrity else {
+			return Ok(());
+		};
+
+		let found = cache::content_hash(path)?;
+		if &found != expected {
+			return IntegrityMismatchSnafu{ url: self.url.clone(), locked: self.to_string(), expected: expected.clone(), found, folder: install_path.display().to_string() }.fail();
+		}
 
-			spinner.success("Done!");
+		Ok(())
+	}
+
+	// the folder install returns, without installing anything
+	pub fn install_folder(&self, cache_path: &Path) -> PathBuf {
+		let root = match self.id {
+			LockedDependencyId::Path => PathBuf::from(&self.url),
+			_ => utils::get_full_path(cache_path).unwrap_or(cache_path.to_path_buf()).join(self.uuid())
+		};
+
+		match &self.subdir {
+			Some(subdir) => root.join(subdir),
+			None => root
 		}
+	}
+
+	// installs the github repository into the cache specified at path 
+	// returns the folder of the package as a string (the subdir of the installation folder, if there is one)
+	// while offline, only dependencies that are already in the cache can be "installed"
+	pub fn install(&self, cache_path: &Path, offline: bool, progress: Line) -> Result<String, SyncError> {
+		let root = self.install_root(cache_path, offline, progress)?;
+		Ok(match &self.subdir {
+			Some(subdir) => Path::new(&root).join(subdir).to_str().expect("Non UTF-8 character in path").to_string(),
+			None => root
+		})
+	}
+
+	// installs the dependency like install, but returns the installation folder itself
+	pub fn install_root(&self, cache_path: &Path, offline: bool, progress: Line) -> Result<String, SyncError> {
+		// local folders are used where they are
+		if self.id == LockedDependencyId::Path {
+			if !Path::new(&self.url).is_dir() {
+				progress.failed("missing");
+				return MissingPathSnafu{ path: self.url.clone() }.fail();
+			}
+			if let Err(error) = self.check_subdir(Path::new(&self.url)) {
+				progress.failed(error.to_string());
+				return Err(error);
+			}
+
+			progress.done(format!("using {}", self.url));
+			return Ok(self.url.clone());
+		}
+
+		fs::create_dir_all(cache_path).context(IoSnafu{ context: format!("creating {:?}", cache_path) })?;
+		let cache_path = utils::get_full_path(cache_path).context(IoSnafu{ context: format!("grabbing full path of {:?}", cache_path) })?;
+		let folder = self.uuid();
+		let install_path = cache_path.join(&folder); // this path should now be absolute
+
+		// the cache folder is named after the patches that were locked, so they have to be the ones on the disk
+		if let Some(patches) = &self.patches {
+			if patch::hash_files(&patches.files)? != patches.sha256 {
+				progress.failed("the patches changed");
+				return PatchesChangedSnafu{ url: self.url.clone() }.fail();
+			}
+		}
+
+		// only one process at a time may install this checkout (i.e. parallel cmake configures sharing a cache)
+		let _lock = CacheLock::acquire(&cache_path, &folder)?;
+
+		// a checkout that exists without being indexed was left behind by a crashed run or predates the index,
+		// keep it only if it's actually checked out at the right commit
+		let indexed = CacheIndex::load(&cache_path)?.find(&folder).is_some();
+		if install_path.exists() && !indexed && self.installed_version(&install_path).is_none() {
+			utils::remove_dir_all(&install_path).context(IoSnafu{ context: format!("removing the partial install {:?}", install_path) })?;
+		}
+
+		let newly_installed = !install_path.exists();
+
+		if newly_installed {
+			if offline {
+				progress.failed("not in the cache");
+				return NotInstalledOfflineSnafu{ url: self.url.clone(), locked: self.to_string() }.fail();
+			}
+
+			// clone next to the cache and move the finished checkout into place, so a crash never leaves a half-cloned folder behind
+			let temp_path = cache::temp_path(&cache_path, &folder)?;
+
+			progress.working(format!("fetching {} ({self})", self.url));
+
+			// a fresh checkout that doesn't match the lock file never makes it into the cache
+			let prepared = self.checkout(&temp_path)
+				.and_then(|_| self.patches.as_ref().map_or(Ok(()), |patches| patch::apply(&temp_path, &patches.files)))
+				.and_then(|_| self.verify(&temp_path, &install_path));
+			if let Err(error) = prepared {
+				progress.failed(error.to_string());
+				let _ = utils::remove_dir_all(&temp_path);
+				return Err(error);
+			}
+
+			fs::rename(&temp_path, &install_path).context(IoSnafu{ context: format!("moving {:?} to {:?}", temp_path, install_path) })?;
+
+			progress.done(format!("installed {self}"));
+		} else {
+			progress.working(format!("verifying {self}"));
+			if let Err(error) = self.verify(&install_path, &install_path) {
+				progress.failed(error.to_string());
+				return Err(error);
+			}
+
+			progress.done(format!("{self} (cached)"));
+		}
+
+		// keep the cache index up to date
+		let commit = self.installed_version(&install_path).unwrap_or_default();
+		CacheIndex::update(&cache_path, |index| {
+			if newly_installed || index.find(&folder).is_none() {
+				let now = utils::unix_time();
+				index.record(&self.package_id(), &self.url, CachedInstall { folder: folder.clone(), object: self.cached_object(), commit, patches: self.patches.as_ref().map(|patches| patches.sha256.clone()), sparse: self.checkout_paths(), installed: now, last_used: now });
+			} else {
+				index.touch(&folder);
+			}
+			Ok(())
+		})?;
 
 		Ok(String::from(install_path.to_str().expect("Non UTF-8 character in path")))
 	}
 }
 
+// true if a path is one of the paths of a sparse checkout, inside of one, or holds one
+fn in_sparse_paths(path: &str, paths: Option<&[String]>) -> bool {
+	let Some(paths) = paths else {
+		return true;
+	};
+
+	paths.iter().any(|sparse| path == sparse || path.starts_with(&format!("{sparse}/")) || sparse.starts_with(&format!("{path}/")))
+}
+
+// checks out the submodules of a repository at the commits its HEAD records, and their submodules after them
+// (only the submodules that are part of a sparse checkout)
+fn checkout_submodules(repository: &Repository, paths: Option<&[String]>) -> Result<(), SyncError> {
+	for mut submodule in repository.submodules().context(GitSnafu)? {
+		// submodules that were never committed (only in .gitmodules) have nothing to check out
+		let Some(oid) = submodule.head_id() else {
+			continue;
+		};
+		if !in_sparse_paths(&submodule.path().to_string_lossy().replace('\\', "/"), paths) {
+			continue;
+		}
+
+		// resolves relative urls against the superproject's origin
+		submodule.init(false).context(GitSnafu)?;
+		let url = repository.config().context(GitSnafu)?
+			.get_string(&format!("submodule.{}.url", submodule.name().unwrap_or_default()))
+			.context(GitSnafu)?;
+
+		let submodule_repository = submodule.repo_init(true).context(GitSnafu)?;
+		let mut origin = match submodule_repository.find_remote("origin") {
+			Ok(origin) => origin,
+			Err(_) => submodule_repository.remote("origin", &url).context(GitSnafu)?
+		};
+
+		// only the recorded commit is fetched where the server allows it, otherwise the whole repository is
+		let mut shallow = auth::fetch_options();
+		shallow.depth(1);
+		if origin.fetch(&[oid.to_string()], Some(&mut shallow), None).is_err() {
+			origin.fetch(&["+refs/heads/*:refs/remotes/origin/*", "+refs/tags/*:refs/tags/*"], Some(&mut auth::fetch_options()), None).context(GitSnafu)?;
+		}
+
+		submodule_repository.reset(&submodule_repository.find_object(oid, None).context(GitSnafu)?, git2::ResetType::Hard, None).context(GitSnafu)?;
+		checkout_submodules(&submodule_repository, None)?;
+	}
+
+	Ok(())
+}
+
+// the commit of every submodule checked out at path (including the submodules of submodules), by path
+pub fn submodule_commits(path: &Path, sparse: Option<&[String]>) -> Result<BTreeMap<String, String>, SyncError> {
+	fn collect(repository: &Repository, prefix: &str, sparse: Option<&[String]>, commits: &mut BTreeMap<String, String>) -> Result<(), SyncError> {
+		for submodule in repository.submodules().context(GitSnafu)? {
+			let path = format!("{prefix}{}", submodule.path().to_string_lossy().replace('\\', "/"));
+			if !in_sparse_paths(&path, sparse) {
+				continue;
+			}
+			if let Some(oid) = submodule.head_id() {
+				commits.insert(path.clone(), oid.to_string());
+			}
+
+			if let Ok(submodule_repository) = submodule.open() {
+				collect(&submodule_repository, &format!("{path}/"), None, commits)?;
+			}
+		}
+		Ok(())
+	}
+
+	let mut commits = BTreeMap::new();
+	collect(&Repository::open(path).context(GitSnafu)?, "", sparse, &mut commits)?;
+	Ok(commits)
+}
+
+// short description of what was locked, used in messages
+impl fmt::Display for LockedDependency {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match (&self.version, &self.id) {
+			(Some(version), _) => write!(f, "{version}"),
+			(None, LockedDependencyId::MainBranch) => write!(f, "main branch"),
+			(None, LockedDependencyId::Branch(branch)) => write!(f, "branch {branch}"),
+			(None, LockedDependencyId::Oid(oid)) => write!(f, "commit {:.7}", oid.to_string()),
+			(None, LockedDependencyId::Archive{ sha256, .. }) => write!(f, "archive sha256 {:.12}", sha256),
+			(None, LockedDependencyId::Path) => write!(f, "path {}", self.url)
+		}
+	}
+}
+
 impl Serialize for LockedDependency {
 	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-    	let size = if let LockedDependencyId::MainBranch = self.id { Some(1) } else { Some(2) };
-        let mut map = serializer.serialize_map(size)?;
-        map.serialize_entry("url", &self.url)?;
+    	let mut size = match self.id {
+    		LockedDependencyId::MainBranch | LockedDependencyId::Path => 1,
+    		LockedDependencyId::Archive{ strip_components: 0, .. } => 2,
+    		LockedDependencyId::Archive{ .. } => 3,
+    		_ => 2
+    	};
+    	if self.version.is_some() {
+    		size += 1;
+    	}
+    	if self.reference.is_some() {
+    		size += 1;
+    	}
+    	if !self.dependencies.is_empty() {
+    		size += 1;
+    	}
+    	if self.integrity.is_some() {
+    		size += 1;
+    	}
+    	if self.submodules.is_some() {
+    		size += 1;
+    	}
+    	if self.patches.is_some() {
+    		size += 1;
+    	}
+    	if self.subdir.is_some() {
+    		size += 1;
+    	}
+    	if self.sparse.is_some() {
+    		size += 1;
+    	}
+
+        let mut map = serializer.serialize_map(Some(size))?;
+        // local folders are written as a path instead of a url
+        let url_key = if self.is_path() { "path" } else { "url" };
+        map.serialize_entry(url_key, &self.url)?;
 
         match &self.id {
         	LockedDependencyId::Branch(name) => {
@@ -140,9 +564,47 @@ impl Serialize for LockedDependency {
         	LockedDependencyId::Oid(oid) => {
         		map.serialize_entry("oid", &oid.to_string())?;
         	},
+        	LockedDependencyId::Archive{ sha256, strip_components } => {
+        		map.serialize_entry("sha256", sha256)?;
+        		if *strip_components > 0 {
+        			map.serialize_entry("strip_components", strip_components)?;
+        		}
+        	},
         	_ => {}
         }
 
+        if let Some(version) = &self.version {
+        	map.serialize_entry("version", version)?;
+        }
+
+        if let Some(reference) = &self.reference {
+        	map.serialize_entry("ref", reference)?;
+        }
+
+        if !self.dependencies.is_empty() {
+        	map.serialize_entry("dependencies", &self.dependencies)?;
+        }
+
+        if let Some(integrity) = &self.integrity {
+        	map.serialize_entry("integrity", integrity)?;
+        }
+
+        if let Some(submodules) = &self.submodules {
+        	map.serialize_entry("submodules", submodules)?;
+        }
+
+        if let Some(patches) = &self.patches {
+        	map.serialize_entry("patches", patches)?;
+        }
+
+        if let Some(subdir) = &self.subdir {
+        	map.serialize_entry("subdir", subdir)?;
+        }
+
+        if let Some(sparse) = &self.sparse {
+        	map.serialize_entry("sparse", sparse)?;
+        }
+
         map.end()
     }
 }
@@ -162,7 +624,7 @@ impl<'de> Visitor<'de> for LockedDependencyVisitor {
     type Value = LockedDependency;
 
     fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-        formatter.write_str("a map containing the url key and optionally either a branch or oid key")
+        formatter.write_str("a map containing the url (or path) key, optionally either a branch, oid or sha256 (and strip_components) key, optionally a version and ref, optionally a dependencies array, optionally an integrity hash, optionally a map of submodule commits, optionally a patch set, optionally a subdir and optionally an array of sparse paths")
     }
 
     fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
@@ -170,14 +632,54 @@ impl<'de> Visitor<'de> for LockedDependencyVisitor {
             A: serde::de::MapAccess<'de>, {
         let mut url: Option<String> = None;
         let mut id = LockedDependencyId::MainBranch;
-
-        while let Some((key, value)) = access.next_entry::<String, String>()? {
+        let mut version: Option<Version> = None;
+        let mut reference: Option<String> = None;
+        let mut dependencies: Vec<String> = Vec::new();
+        let mut integrity: Option<String> = None;
+        let mut submodules: Option<BTreeMap<String, String>> = None;
+        let mut patches: Option<PatchSet> = None;
+        let mut subdir: Option<String> = None;
+        let mut sparse: Option<Vec<String>> = None;
+        let mut strip_components: usize = 0;
+
+        while let Some(key) = access.next_key::<String>()? {
         	if key == "url" {
-        		url = Some(String::from(value));
+        		url = Some(access.next_value::<String>()?);
+        	} else if key == "path" {
+        		url = Some(access.next_value::<String>()?);
+        		id = LockedDependencyId::Path;
         	} else if key == "branch" {
-        		id = LockedDependencyId::Branch(String::from(value));
+        		id = LockedDependencyId::Branch(access.next_value::<String>()?);
         	} else if key == "oid" {
-        		id = LockedDependencyId::Oid(git2::Oid::from_str(&value).unwrap());
+        		let value = access.next_value::<String>()?;
+        		id = LockedDependencyId::Oid(git2::Oid::from_str(&value).map_err(A::Error::custom)?);
+        	} else if key == "sha256" {
+        		// a hand edited or badly merged hash is an error here rather than a panic or a wrong hash later
+        		let sha256 = access.next_value::<String>()?;
+        		if !utils::is_sha256(&sha256) {
+        			return Err(A::Error::custom(format!("{sha256} isn't a sha256 hash (64 hexadecimal digits)")));
+        		}
+        		id = LockedDependencyId::Archive{ sha256: sha256.to_lowercase(), strip_components: 0 };
+        	} else if key == "strip_components" {
+        		strip_components = access.next_value::<usize>()?;
+        	} else if key == "version" {
+        		version = Some(access.next_value::<Version>()?);
+        	} else if key == "ref" {
+        		reference = Some(access.next_value::<String>()?);
+        	} else if key == "dependencies" {
+        		dependencies = access.next_value::<Vec<String>>()?;
+        	} else if key == "integrity" {
+        		integrity = Some(access.next_value::<String>()?);
+        	} else if key == "submodules" {
+        		submodules = Some(access.next_value::<BTreeMap<String, String>>()?);
+        	} else if key == "patches" {
+        		patches = Some(access.next_value::<PatchSet>()?);
+        	} else if key == "subdir" {
+        		subdir = Some(access.next_value::<String>()?);
+        	} else if key == "sparse" {
+        		sparse = Some(access.next_value::<Vec<String>>()?);
+        	} else {
+        		access.next_value::<serde::de::IgnoredAny>()?;
         	}
         }
 
@@ -185,7 +687,11 @@ impl<'de> Visitor<'de> for LockedDependencyVisitor {
         	return Err(A::Error::missing_field("url"));
         }
 
-        Ok(LockedDependency { url: url.unwrap(), id: id })
+        if let LockedDependencyId::Archive{ strip_components: strip, .. } = &mut id {
+        	*strip = strip_components;
+        }
+
+        Ok(LockedDependency { url: url.unwrap(), id: id, version: version, reference: reference, dependencies: dependencies, integrity: integrity, submodules: submodules, patches: patches, subdir: subdir, sparse: sparse })
     }
 }
 
@@ -201,14 +707,84 @@ impl LockFile {
 		LockFile { lockfile_version: 0, dependencies: BTreeMap::new() }
 	}
 
-	pub fn merge(&mut self, other: &LockFile) {
+	// adds the dependencies of another lock file, both files must agree on the dependencies they share
+	pub fn merge(&mut self, other: &LockFile) -> Result<(), SyncError> {
 		for (name, dependency) in &other.dependencies {
-			if !self.dependencies.contains_key(name) {
-				self.dependencies.insert(name.to_owned(), dependency.clone());
+			match self.dependencies.get_mut(name) {
+				Some(existing) if !existing.same_source(dependency) => {
+					return LockMergeSnafu{ name: name.clone(), existing: existing.to_string(), other: dependency.to_string() }.fail();
+				},
+				Some(existing) => {
+					if existing.integrity.is_none() {
+						existing.integrity = dependency.integrity.clone();
+						existing.submodules = dependency.submodules.clone();
+					}
+				},
+				None => {
+					self.dependencies.insert(name.to_owned(), dependency.clone());
+				}
 			}
 		}
+
+		Ok(())
 	} 
 
+	// local folders and patches are written relative to the lock file's folder, so the lock file can be committed
+	pub fn relative_paths(&mut self, folder: &Path) {
+		let relative = |path: &mut String| {
+			if let Some(relative) = utils::relative_path(Path::new(path.as_str()), folder) {
+				*path = relative.to_string_lossy().replace('\\', "/");
+			}
+		};
+
+		for dependency in self.dependencies.values_mut() {
+			if dependency.is_path() {
+				relative(&mut dependency.url);
+			}
+			for patch in dependency.patches.iter_mut().flat_map(|patches| patches.files.iter_mut()) {
+				relative(patch);
+			}
+		}
+	}
+
+	// turns the paths written by relative_paths back into absolute paths
+	pub fn absolute_paths(&mut self, folder: &Path) {
+		let absolute = |path: &mut String| {
+			if Path::new(path.as_str()).is_relative() {
+				let joined = folder.join(path.as_str());
+				*path = utils::get_full_path(&joined).unwrap_or(joined).to_string_lossy().to_string();
+			}
+		};
+
+		for dependency in self.dependencies.values_mut() {
+			if dependency.is_path() {
+				absolute(&mut dependency.url);
+			}
+			for patch in dependency.patches.iter_mut().flat_map(|patches| patches.files.iter_mut()) {
+				absolute(patch);
+			}
+		}
+	}
+
+	// returns the names of the root dependencies and every dependency reachable from them (in breadth-first order)
+	pub fn closure(&self, roots: &[&str]) -> Vec<String> {
+		let mut names: Vec<String> = roots.iter().map(|name| name.to_string()).collect();
+
+		let mut i = 0;
+		while i < names.len() {
+			if let Some(dependency) = self.dependencies.get(&names[i]) {
+				for child in &dependency.dependencies {
+					if !names.contains(child) {
+						names.push(child.clone());
+					}
+				}
+			}
+			i += 1;
+		}
+
+		names
+	}
+
 	/*
 	fn include_feature(&mut self, pkg_name: &str, feature: &str) {
 		if !self.features.contains_key(pkg_name) {
@@ -218,4 +794,132 @@ impl LockFile {
 		let features_vec: &mut Vec<String> = self.features.get_mut(pkg_name).unwrap();
 		features_vec.push(feature.to_string());
 	}*/
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked_with_deps(url: &str, deps: &[&str]) -> LockedDependency {
+        let mut locked = LockedDependency::new(url.to_string(), LockedDependencyId::MainBranch);
+        locked.dependencies = deps.iter().map(|dep| dep.to_string()).collect();
+        locked
+    }
+
+    #[test]
+    fn check_dependencies_round_trip() {
+        let locked = locked_with_deps("https://github.com/gabime/spdlog.git", &["fmt"]);
+        let json = serde_json::to_string(&locked).unwrap();
+        assert_eq!(json, r#"{"url":"https://github.com/gabime/spdlog.git","dependencies":["fmt"]}"#);
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.dependencies, vec!["fmt".to_string()]);
+    }
+
+    #[test]
+    fn check_integrity_round_trip() {
+        let mut locked = LockedDependency::new("https://github.com/gabime/spdlog.git".to_string(), LockedDependencyId::Oid(git2::Oid::from_str("27cb4c76708608465c413f6d0e6b8d99a4d84302").unwrap()));
+        locked.integrity = Some("sha256-00ff".to_string());
+        let json = serde_json::to_string(&locked).unwrap();
+        assert_eq!(json, r#"{"url":"https://github.com/gabime/spdlog.git","oid":"27cb4c76708608465c413f6d0e6b8d99a4d84302","integrity":"sha256-00ff"}"#);
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.integrity.as_deref(), Some("sha256-00ff"));
+    }
+
+    #[test]
+    fn check_submodules_round_trip() {
+        let without = LockedDependency::new("https://github.com/ocornut/imgui.git".to_string(), LockedDependencyId::Oid(git2::Oid::from_str("27cb4c76708608465c413f6d0e6b8d99a4d84302").unwrap()));
+        let mut locked = without.clone();
+        locked.submodules = Some(BTreeMap::from([("third_party/freetype".to_string(), "0123456789abcdef0123456789abcdef01234567".to_string())]));
+        assert_eq!(locked.uuid(), "gh-ocornut-imgui-27cb4c76708608465c413f6d0e6b8d99a4d84302-submodules");
+        assert!(!locked.same_source(&without));
+
+        let json = serde_json::to_string(&locked).unwrap();
+        assert_eq!(json, r#"{"url":"https://github.com/ocornut/imgui.git","oid":"27cb4c76708608465c413f6d0e6b8d99a4d84302","submodules":{"third_party/freetype":"0123456789abcdef0123456789abcdef01234567"}}"#);
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert!(parsed.same_source(&locked));
+        assert_eq!(parsed.submodules, locked.submodules);
+    }
+
+    #[test]
+    fn check_sparse_round_trip() {
+        let without = LockedDependency::new("https://github.com/google/googletest.git".to_string(), LockedDependencyId::Oid(git2::Oid::from_str("27cb4c76708608465c413f6d0e6b8d99a4d84302").unwrap()));
+        let mut locked = without.clone();
+        locked.subdir = Some("googlemock".to_string());
+        assert_eq!(locked.uuid(), without.uuid());
+        assert!(!locked.same_source(&without));
+
+        locked.sparse = Some(vec!["googlemock".to_string(), "googletest".to_string()]);
+        assert!(locked.uuid().starts_with("gh-google-googletest-27cb4c76708608465c413f6d0e6b8d99a4d84302-sparse-"));
+        assert_eq!(locked.checkout_paths().unwrap(), vec!["googlemock", "googletest", ".gitmodules", "googlemock/wares.toml"]);
+
+        let json = serde_json::to_string(&locked).unwrap();
+        assert_eq!(json, r#"{"url":"https://github.com/google/googletest.git","oid":"27cb4c76708608465c413f6d0e6b8d99a4d84302","subdir":"googlemock","sparse":["googlemock","googletest"]}"#);
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert!(parsed.same_source(&locked));
+        assert_eq!(parsed.uuid(), locked.uuid());
+        assert!(in_sparse_paths("googletest/third_party/abseil", parsed.sparse.as_deref()));
+        assert!(!in_sparse_paths("ci", parsed.sparse.as_deref()));
+    }
+
+    #[test]
+    fn check_archive_round_trip() {
+        let sha256 = "9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23";
+        let locked = LockedDependency::new("https://zlib.net/zlib-1.3.1.tar.gz".to_string(), LockedDependencyId::Archive{ sha256: sha256.to_string(), strip_components: 1 });
+        assert_eq!(locked.uuid(), "archive-zlib-1.3.1.tar.gz-407a86e6571b-9a93b2b7dfdac77c-strip1");
+
+        let json = serde_json::to_string(&locked).unwrap();
+        assert_eq!(json, format!(r#"{{"url":"https://zlib.net/zlib-1.3.1.tar.gz","sha256":"{sha256}","strip_components":1}}"#));
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert!(parsed.same_source(&locked));
+    }
+
+    #[test]
+    fn check_protocol_ids() {
+        assert_eq!(id_of("https://github.com/gabime/spdlog.git", false), "gh-gabime-spdlog");
+        assert_eq!(id_of("git@github.com:gabime/spdlog.git", false), "ssh-gh-gabime-spdlog");
+        assert_eq!(id_of("ssh://git@example.com/tools/build.git", false), "ssh-example.com_tools_build");
+        assert_eq!(id_of("https://example.com/tools/build.git", false), "example.com_tools_build");
+
+        // with share_protocols set, every protocol shares the https id
+        assert_eq!(id_of("git@github.com:gabime/spdlog.git", true), "gh-gabime-spdlog");
+        assert_eq!(id_of("ssh://git@example.com/tools/build.git", true), "example.com_tools_build");
+    }
+
+    #[test]
+    fn check_bad_archive_hash() {
+        for sha256 in ["9a93b2b7dfdac77c", "9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df2", "\u{e9}a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df2", "za93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23"] {
+            let json = format!(r#"{{"url":"https://zlib.net/zlib-1.3.1.tar.gz","sha256":"{sha256}"}}"#);
+            assert!(serde_json::from_str::<LockedDependency>(&json).is_err(), "{sha256}");
+        }
+    }
+
+    #[test]
+    fn check_path_round_trip() {
+        let mut lockfile = LockFile::new();
+        lockfile.dependencies.insert("mycore".to_string(), LockedDependency::new("/home/me/libs/mycore".to_string(), LockedDependencyId::Path));
+
+        lockfile.relative_paths(Path::new("/home/me/game"));
+        let json = serde_json::to_string(&lockfile.dependencies["mycore"]).unwrap();
+        assert_eq!(json, r#"{"path":"../libs/mycore"}"#);
+
+        let parsed: LockedDependency = serde_json::from_str(&json).unwrap();
+        assert!(parsed.is_path());
+        assert!(!parsed.has_fixed_contents());
+    }
+
+    #[test]
+    fn check_closure() {
+        let mut lockfile = LockFile::new();
+        lockfile.dependencies.insert("engine".to_string(), locked_with_deps("https://github.com/a/engine.git", &["fmt", "glm"]));
+        lockfile.dependencies.insert("fmt".to_string(), locked_with_deps("https://github.com/fmtlib/fmt.git", &[]));
+        lockfile.dependencies.insert("glm".to_string(), locked_with_deps("https://github.com/g-truc/glm.git", &["engine"]));
+        lockfile.dependencies.insert("unused".to_string(), locked_with_deps("https://github.com/a/unused.git", &[]));
+
+        assert_eq!(lockfile.closure(&["engine"]), vec!["engine", "fmt", "glm"]);
+    }
+}
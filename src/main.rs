@@ -1,9 +1,19 @@
-use std::{collections::BTreeMap, env};
+use std::collections::BTreeMap;
 
-use wares_native::sync;
+use wares_native::{utils, SyncRunner, SyncError};
+use wares_native::cache::{self, CacheIndex};
+use wares_native::check;
+use wares_native::edit;
+use wares_native::doctor::{self, Check, Status};
+use wares_native::init::{self, BuildSystem, Change};
+use wares_native::lock::LockFile;
+use wares_native::manifest::RefSource;
+use wares_native::outdated;
+use wares_native::why::Why;
+use wares_native::tree::{self, Tree};
 
-use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use clap::{Parser, Subcommand, ValueEnum};
 
 use colored::Colorize;
 
@@ -23,6 +33,9 @@ enum Command {
 	//      --current="path/to/current/folder"			; path to the folder that contains the current wares.toml file
 	//      --cache="path/to/cache"						; path to the cache directory (defaults to WARES_CACHE environment variable, or, failing that ./wares_cache in the root directory)
 	//      --first?									; is this the first call to wares sync for this run? (i.e. should the lock file be considered outdated)
+	//      --offline									; resolve and install from the cache only (defaults to the WARES_OFFLINE environment variable)
+	//      --jobs=8									; how many dependencies to lock/install at the same time (defaults to the WARES_JOBS environment variable, or the number of cpus)
+	//      --update-hashes								; record new integrity hashes in wares.lock instead of failing when the installed files don't match
 	//      --override:xxx="path/to/other/dir"          ; override the installation directory for a specific dependency (xxx)
 	//      --override=glfw:"path/to/glfw/dir"
 	Sync {
@@ -41,6 +54,18 @@ enum Command {
 		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
 		cache: Option<PathBuf>,
 
+		// don't touch the network, resolve everything from the cache instead
+		#[arg(long, help = "resolve and install dependencies from the cache only (or set WARES_OFFLINE)")]
+		offline: bool,
+
+		// how many dependencies to lock and install at the same time
+		#[arg(long, short, value_name = "N", help = "how many dependencies to lock and install at the same time (or set WARES_JOBS)")]
+		jobs: Option<usize>,
+
+		// accept the files that are installed now, for when an upstream change is known to be fine
+		#[arg(long, help = "record new integrity hashes in wares.lock instead of failing when they don't match")]
+		update_hashes: bool,
+
 		// is this the first time we're running the sync command? (used for interfacing with cmake, so don't show it to the user)
 		#[arg (hide = true, long, short)]
 		first: bool,
@@ -53,6 +78,481 @@ enum Command {
 		//#[arg(long)]
 		#[arg(last = true, value_name = "OVERRIDES")]
 		var_args: Vec<String>
+	},
+
+	// Sets up a project to use wares
+	// init
+	//      --cmake										; also add get_wares.cmake and include it in CMakeLists.txt
+	//      --premake									; also add get_wares.lua and include it in premake5.lua
+	//      (the build system is detected from the build script when neither is given)
+	#[command(about = "set up a project to use wares")]
+	Init {
+		#[arg(long, conflicts_with = "premake", help = "set up a cmake project (CMakeLists.txt)")]
+		cmake: bool,
+
+		#[arg(long, help = "set up a premake project (premake5.lua)")]
+		premake: bool,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>
+	},
+
+	// Adds a dependency to wares.toml
+	// add gh:gabime/spdlog@^1.14					; the dependency, pinned to ^<latest release> when it has no specifier
+	//      --group="dev-dependencies"				; the group to add it to (defaults to dependencies)
+	//      --name="spdlog"							; the name to give it (defaults to the repository name)
+	//      --current="path/to/current/folder"		; path to the folder that contains the wares.toml file
+	#[command(about = "add a dependency to wares.toml")]
+	Add {
+		// the dependency string (gh:gabime/spdlog@^1.14, git:<url>, url:<archive>, path:<folder>)
+		spec: String,
+
+		#[arg(long, short, default_value = "dependencies", help = "the group to add the dependency to")]
+		group: String,
+
+		#[arg(long, short, help = "the name of the dependency (defaults to the repository name)")]
+		name: Option<String>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>
+	},
+
+	// Removes a dependency from wares.toml (every group it's in) and from wares.lock
+	// remove spdlog
+	//      --root="path/to/main/folder"				; path to the folder that contains the wares.lock file
+	//      --current="path/to/current/folder"		; path to the folder that contains the wares.toml file
+	#[command(about = "remove a dependency from wares.toml and wares.lock")]
+	Remove {
+		name: String,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>
+	},
+
+	// Locks some (or all) of the dependencies again, leaving the others as they are in wares.lock
+	// update [spdlog fmt]							; the packages to update (every package if none are given)
+	//      --precise=1.14.0						; the exact version to lock the (single) package to
+	//      --group=dev-dependencies				; the extra dep groups to lock, like sync
+	#[command(about = "lock some (or all) dependencies again, without touching the others")]
+	Update {
+		packages: Vec<String>,
+
+		#[arg(long, value_name = "VERSION", help = "lock the package to exactly this version")]
+		precise: Option<semver::Version>,
+
+		#[arg(long, short, value_name = "GROUP", help = "an extra dependency group to lock (can be repeated)")]
+		group: Vec<String>,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>,
+
+		#[arg(long, help = "resolve and install dependencies from the cache only (or set WARES_OFFLINE)")]
+		offline: bool,
+
+		#[arg(long, short, value_name = "N", help = "how many dependencies to lock and install at the same time (or set WARES_JOBS)")]
+		jobs: Option<usize>
+	},
+
+	// Prints the dependency graph of every group, from wares.toml and wares.lock
+	// tree
+	//      --group=dependencies						; only show this group (can be repeated, defaults to every group)
+	//      --depth=1									; how many levels of dependencies to show
+	//      --invert=fmt								; show what depends on a package instead, up to wares.toml
+	//      --format=text|json|dot
+	#[command(about = "print the dependency graph of each group")]
+	Tree {
+		#[arg(long, short, value_name = "GROUP", help = "only show this group (can be repeated)")]
+		group: Vec<String>,
+
+		#[arg(long, short, value_name = "N", help = "how many levels of dependencies to show")]
+		depth: Option<usize>,
+
+		#[arg(long, short, value_name = "PACKAGE", help = "show the packages that depend on a package instead")]
+		invert: Option<String>,
+
+		#[arg(long, short, value_enum, default_value_t = TreeFormat::Text)]
+		format: TreeFormat,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>
+	},
+
+	// Explains why a package is part of the build: every chain of dependencies from wares.toml to it, and where it's used from
+	// why fmt
+	//      --override:xxx="path/to/other/dir"          ; the same overrides that are passed to sync
+	#[command(about = "show why a package is part of the build")]
+	Why {
+		package: String,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>,
+
+		// path overrides for dependencies
+		#[arg(last = true, value_name = "OVERRIDES")]
+		var_args: Vec<String>
+	},
+
+	// Lists the dependencies with a newer version than the one locked, from the tags of their repositories
+	// outdated
+	//      --group=dependencies						; only check this group (can be repeated, defaults to every group)
+	//      --format=text|json
+	//      --offline									; only look at the tags in the cache
+	#[command(about = "list the dependencies that have newer versions")]
+	Outdated {
+		#[arg(long, short, value_name = "GROUP", help = "only check this group (can be repeated)")]
+		group: Vec<String>,
+
+		#[arg(long, short, value_enum, default_value_t = ReportFormat::Text)]
+		format: ReportFormat,
+
+		#[arg(long, help = "list every dependency with a version requirement, even if it's up to date")]
+		all: bool,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>,
+
+		#[arg(long, help = "only look at the versions in the cache (or set WARES_OFFLINE)")]
+		offline: bool
+	},
+
+	// Checks wares.toml for mistakes: it's parsed strictly, then linted for floating dependencies, repositories
+	// under two names and groups the build scripts never ask for
+	// check
+	//      --format=text|json
+	#[command(about = "check wares.toml for mistakes")]
+	Check {
+		#[arg(long, short, value_enum, default_value_t = ReportFormat::Text)]
+		format: ReportFormat,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>
+	},
+
+	// Checks the project, the cache and the build system scripts for problems
+	// doctor
+	//      --format=text|json
+	//      --offline									; don't check that the remotes can be reached
+	#[command(about = "check the project and the cache for installation problems")]
+	Doctor {
+		#[arg(long, short, value_enum, default_value_t = ReportFormat::Text)]
+		format: ReportFormat,
+
+		// Sets the root directory (where the lockfile is stored) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.lock")]
+		root: Option<PathBuf>,
+
+		// Sets the current directory (where wares.toml is located) (defaults to cwd)
+		#[arg(short, long, value_name = "DIRECTORY", help = "the directory containing wares.toml")]
+		current: Option<PathBuf>,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>,
+
+		#[arg(long, help = "don't check that the remotes can be reached (or set WARES_OFFLINE)")]
+		offline: bool
+	},
+
+	// Inspects and trims the package cache
+	// cache list|info|clean|prune|verify
+	//      --cache="path/to/cache"						; path to the cache directory (defaults to WARES_CACHE environment variable, or, failing that ./wares_cache)
+	#[command(about = "inspect and trim the package cache")]
+	Cache {
+		#[command(subcommand)]
+		command: CacheCommand,
+
+		// Sets the directory that the cache is stored in
+		#[arg(long, short = 'a', global = true, value_name = "DIRECTORY", help = "the cache directory")]
+		cache: Option<PathBuf>
+	}
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TreeFormat {
+	Text,
+	Json,
+	Dot
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum ReportFormat {
+	Text,
+	Json
+}
+
+#[derive(Subcommand)]
+enum CacheCommand {
+	// cache list
+	#[command(about = "list every checkout in the cache")]
+	List,
+
+	// cache info spdlog
+	#[command(about = "show the checkouts of one package (by id, url or repository name)")]
+	Info {
+		package: String
+	},
+
+	// cache clean [spdlog]
+	#[command(about = "remove the checkouts of one package, or of every package")]
+	Clean {
+		package: Option<String>
+	},
+
+	// cache prune --unused-since 30d
+	#[command(about = "remove the checkouts that haven't been used for a while")]
+	Prune {
+		#[arg(long, value_name = "DURATION", help = "how long a checkout has to be unused before it's removed (i.e. 30d, 12h)")]
+		unused_since: String
+	},
+
+	// cache verify
+	#[command(about = "check that every checkout is still at its recorded commit with a clean worktree")]
+	Verify
+}
+
+// reads the --override:xxx="path/to/other/dir" arguments
+fn parse_overrides(var_args: &[String]) -> BTreeMap<String, String> {
+	let mut overrides: BTreeMap<String, String> = BTreeMap::new();
+
+	for arg in var_args {
+		if arg.starts_with("--override:") {
+			let split_arg: Vec<&str> = arg[11..].splitn(2, '=').collect();
+			overrides.insert(split_arg[0].to_string(), split_arg[1].to_string());
+		}
+	}
+
+	overrides
+}
+
+fn print_install(cache_dir: &Path, install: &cache::CachedInstall) {
+	let now = utils::unix_time();
+	println!("    {} {} ({}, last used {} day(s) ago)",
+		install.object.to_string().green(),
+		cache_dir.join(&install.folder).display().to_string().yellow(),
+		utils::format_size(utils::dir_size(&cache_dir.join(&install.folder))),
+		now.saturating_sub(install.last_used) / (60 * 60 * 24));
+}
+
+fn run_cache(command: &CacheCommand, cache_dir: &Path) -> Result<(), SyncError> {
+	let index = CacheIndex::load(cache_dir)?;
+
+	match command {
+		CacheCommand::List => {
+			for dependency in index.dependencies.values() {
+				println!("{} {}", dependency.id.cyan(), dependency.url);
+				for install in &dependency.installed {
+					print_install(cache_dir, install);
+				}
+			}
+			println!("{} {}", "Total".green(), utils::format_size(utils::dir_size(cache_dir)));
+		},
+		CacheCommand::Info { package } => {
+			let matches = index.matching(package);
+			if matches.is_empty() {
+				println!("{} isn't in the cache", package.yellow());
+			}
+			for dependency in matches {
+				println!("{} {}", dependency.id.cyan(), dependency.url);
+				for install in &dependency.installed {
+					print_install(cache_dir, install);
+					println!("        commit {}, installed {} day(s) ago", install.commit, utils::unix_time().saturating_sub(install.installed) / (60 * 60 * 24));
+					if let Some(patches) = &install.patches {
+						println!("        patched (patch set sha256 {:.12})", patches);
+					}
+				}
+			}
+		},
+		CacheCommand::Clean { package } => {
+			// only checkouts recorded in the index are removed, in case the cache shares a folder with other files
+			let folders: Vec<String> = match package {
+				Some(package) => index.matching(package).iter().flat_map(|dependency| dependency.installed.iter()).map(|install| install.folder.clone()).collect(),
+				None => index.dependencies.values().flat_map(|dependency| dependency.installed.iter()).map(|install| install.folder.clone()).collect()
+			};
+
+			for folder in &folders {
+				println!("{} {}", "Removing".red(), folder);
+				cache::remove_install(cache_dir, folder)?;
+			}
+			println!("{} {} checkout(s)", "Removed".green(), folders.len());
+		},
+		CacheCommand::Prune { unused_since } => {
+			let Some(duration) = utils::parse_duration(unused_since) else {
+				return Err(SyncError::InvalidDuration{ value: unused_since.clone() });
+			};
+
+			let cutoff = utils::unix_time().saturating_sub(duration);
+			let folders: Vec<String> = index.unused_since(cutoff).iter().map(|install| install.folder.clone()).collect();
+
+			let mut freed = 0;
+			for folder in &folders {
+				freed += utils::dir_size(&cache_dir.join(folder));
+				println!("{} {}", "Removing".red(), folder);
+				cache::remove_install(cache_dir, folder)?;
+			}
+			println!("{} {} checkout(s), freeing {}", "Removed".green(), folders.len(), utils::format_size(freed));
+		},
+		CacheCommand::Verify => {
+			let mut problems = 0;
+			for install in index.dependencies.values().flat_map(|dependency| dependency.installed.iter()) {
+				match cache::verify_install(cache_dir, install) {
+					Some(problem) => {
+						problems += 1;
+						println!("{} {}: {}", "FAIL".red(), install.folder, problem);
+					},
+					None => println!("{} {}", "ok".green(), install.folder)
+				}
+			}
+
+			if problems > 0 {
+				println!("{} checkout(s) failed verification, remove them with `wares cache clean <package>`", problems);
+				std::process::exit(1);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn run_tree(manifest_file: &Path, lock_file: &Path, cache_dir: &Path, groups: &[String], depth: Option<usize>, invert: Option<&str>, format: TreeFormat) -> Result<(), SyncError> {
+	let manifest = wares_native::read_manifest(manifest_file)?;
+	// nothing is locked yet without a lock file, the tree still shows what the manifest asks for
+	let lockfile = if lock_file.exists() { wares_native::read_lockfile(lock_file)? } else { LockFile::new() };
+	let tree = Tree::new(&manifest, &lockfile, cache_dir, depth);
+
+	if let Some(package) = invert {
+		let node = tree.invert(package)?;
+		match format {
+			TreeFormat::Text => print!("{}", tree::render_text(&tree::heading(&node), &node.children)),
+			TreeFormat::Json => println!("{}", tree::render_json(&node)?),
+			TreeFormat::Dot => print!("{}", tree::render_dot(&[(node.name.clone(), node.children)]))
+		}
+		return Ok(());
+	}
+
+	let groups: Vec<String> = if groups.is_empty() { manifest.dependencies.keys().cloned().collect() } else { groups.to_vec() };
+	let nodes: Vec<(String, Vec<tree::Node>)> = groups.iter().map(|group| (group.clone(), tree.group(group))).collect();
+
+	match format {
+		TreeFormat::Text => {
+			for (group, nodes) in &nodes {
+				print!("{}", tree::render_text(&format!("[{group}]").cyan().to_string(), nodes));
+			}
+		},
+		TreeFormat::Json => {
+			let nodes: BTreeMap<String, Vec<tree::Node>> = nodes.into_iter().collect();
+			println!("{}", tree::render_json(&nodes)?);
+		},
+		TreeFormat::Dot => {
+			let nodes: Vec<(String, Vec<tree::Node>)> = nodes.into_iter().map(|(group, nodes)| (format!("[{group}]"), nodes)).collect();
+			print!("{}", tree::render_dot(&nodes));
+		}
+	}
+
+	Ok(())
+}
+
+fn run_why(manifest_file: &Path, lock_file: &Path, cache_dir: &Path, package: &str, overrides: &BTreeMap<String, String>) -> Result<(), SyncError> {
+	let manifest = wares_native::read_manifest(manifest_file)?;
+	let lockfile = if lock_file.exists() { wares_native::read_lockfile(lock_file)? } else { LockFile::new() };
+
+	println!("{}", Why::new(&manifest, &lockfile, cache_dir, overrides).explain(package)?);
+	Ok(())
+}
+
+// returns the number of dependencies whose versions couldn't be listed
+fn run_outdated(manifest_file: &Path, lock_file: &Path, cache_dir: &Path, groups: &[String], format: ReportFormat, all: bool, offline: bool) -> Result<usize, SyncError> {
+	let manifest = wares_native::read_manifest(manifest_file)?;
+	let lockfile = if lock_file.exists() { wares_native::read_lockfile(lock_file)? } else { LockFile::new() };
+	let refs = if offline { RefSource::Cache(cache_dir) } else { RefSource::Remote };
+
+	let mut rows = outdated::report(&manifest, &lockfile, groups, refs);
+	if !all {
+		rows.retain(|row| row.is_outdated());
+	}
+
+	match format {
+		ReportFormat::Text if rows.is_empty() => println!("{}", "Every dependency is up to date".green()),
+		ReportFormat::Text => print!("{}", outdated::render_table(&rows)),
+		ReportFormat::Json => println!("{}", tree::render_json(&rows)?)
+	}
+
+	let failed: Vec<&outdated::Outdated> = rows.iter().filter(|row| row.error.is_some()).collect();
+	if format == ReportFormat::Text {
+		for row in &failed {
+			eprintln!("{} {}: {}", "Couldn't check".red(), row.name, row.error.as_deref().unwrap_or_default());
+		}
+	}
+
+	Ok(failed.len())
+}
+
+// reports an error on stderr and exits with a failure, so scripts and cron jobs notice
+fn fail(error: SyncError) -> ! {
+	eprintln!("{error}");
+	std::process::exit(1);
+}
+
+// prints a pass/warn/fail checklist
+fn print_checks(checks: &[Check], format: ReportFormat) {
+	match format {
+		ReportFormat::Text => {
+			for check in checks {
+				let status = match check.status {
+					Status::Pass => "pass".green(),
+					Status::Warn => "warn".yellow(),
+					Status::Fail => "fail".red()
+				};
+				println!("[{status}] {} {}", check.subject.cyan(), check.message);
+			}
+		},
+		ReportFormat::Json => match tree::render_json(&checks) {
+			Ok(json) => println!("{json}"),
+			Err(error) => println!("{error}")
+		}
 	}
 }
 
@@ -60,16 +560,9 @@ fn main() {
 	let cli = Cli::parse();
 
 	match &cli.command {
-		Command::Sync { enabled_groups, root, current, cache, first, backend, var_args } => {
+		Command::Sync { enabled_groups, root, current, cache, offline, jobs, update_hashes, first, backend, var_args } => {
 			// read in any overrides
-			let mut overrides: BTreeMap<String, String> = BTreeMap::new();
-
-			for arg in var_args {
-				if arg.starts_with("--override:") {
-					let split_arg: Vec<&str> = arg[11..].splitn(2, '=').collect();
-					overrides.insert(split_arg[0].to_string(), split_arg[1].to_string());
-				}
-			}
+			let overrides = parse_overrides(var_args);
 
 			// convert the manifest folder to a manifest file path
 			let mut manifest_file: PathBuf = root.clone().unwrap_or(PathBuf::from("./"));
@@ -85,6 +578,13 @@ fn main() {
 			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
 
 			let mut sync_runner = SyncRunner::build(&enabled_groups, &manifest_file, &lock_file, &cache_dir, !*backend, overrides, *first);
+			if *offline {
+				sync_runner.set_offline(true);
+			}
+			if let Some(jobs) = jobs {
+				sync_runner.set_jobs(*jobs);
+			}
+			sync_runner.set_update_hashes(*update_hashes);
 
 			// force sync if backend output is not set
 			match sync_runner.sync() {
@@ -97,10 +597,130 @@ fn main() {
 						}
 					}
 				},
-				Err(error) => {
-					println!("{error}");
-				},
+				Err(error) => fail(error)
 			} 
+		},
+		Command::Init { cmake, premake, current } => {
+			let folder = current.clone().unwrap_or(PathBuf::from("./"));
+			let build_system = match (cmake, premake) {
+				(true, _) => Some(BuildSystem::CMake),
+				(_, true) => Some(BuildSystem::Premake),
+				_ => BuildSystem::detect(&folder)
+			};
+
+			match init::init(&folder, build_system) {
+				Ok(changes) => {
+					for change in changes {
+						match change {
+							Change::Created(file) => println!("{} {}", "Created".green(), file.yellow()),
+							Change::Patched(file) => println!("{} {}", "Updated".green(), file.yellow()),
+							Change::Unchanged(file) => println!("{} {} (it's already set up)", "Skipped".cyan(), file.yellow())
+						}
+					}
+				},
+				Err(error) => fail(error)
+			}
+		},
+		Command::Add { spec, group, name, current } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+
+			match edit::add(&manifest_file, spec, group, name.as_deref()) {
+				Ok(added) => println!("{} {} = \"{}\" to [{}]", "Added".green(), added.name.cyan(), added.spec, added.group),
+				Err(error) => fail(error)
+			}
+		},
+		Command::Remove { name, root, current } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+
+			match edit::remove(&manifest_file, &lock_file, name) {
+				Ok(removed) => {
+					println!("{} {} from [{}]", "Removed".green(), name.cyan(), removed.groups.join("], ["));
+					for package in removed.unlocked {
+						println!("{} {} from wares.lock", "Unlocked".green(), package.cyan());
+					}
+				},
+				Err(error) => fail(error)
+			}
+		},
+		Command::Update { packages, precise, group, root, current, cache, offline, jobs } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			// this is the only run using the lock file, so nothing is pinned by a parent project
+			let mut sync_runner = SyncRunner::build(group, &manifest_file, &lock_file, &cache_dir, true, BTreeMap::new(), true);
+			if *offline {
+				sync_runner.set_offline(true);
+			}
+			if let Some(jobs) = jobs {
+				sync_runner.set_jobs(*jobs);
+			}
+			sync_runner.set_relock(packages.clone(), precise.clone());
+
+			match sync_runner.sync() {
+				Ok(map) => {
+					for (name, folder) in map {
+						println!("{} installed to: {}", name.green(), folder.yellow());
+					}
+				},
+				Err(error) => fail(error)
+			}
+		},
+		Command::Tree { group, depth, invert, format, root, current, cache } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			if let Err(error) = run_tree(&manifest_file, &lock_file, &cache_dir, group, *depth, invert.as_deref(), *format) {
+				fail(error);
+			}
+		},
+		Command::Why { package, root, current, cache, var_args } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			if let Err(error) = run_why(&manifest_file, &lock_file, &cache_dir, package, &parse_overrides(var_args)) {
+				fail(error);
+			}
+		},
+		Command::Outdated { group, format, all, root, current, cache, offline } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			match run_outdated(&manifest_file, &lock_file, &cache_dir, group, *format, *all, *offline || utils::offline_fallback()) {
+				Ok(0) => {},
+				Ok(_) => std::process::exit(1),
+				Err(error) => fail(error)
+			}
+		},
+		Command::Doctor { format, root, current, cache, offline } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+			let lock_file = root.clone().unwrap_or(PathBuf::from("./")).join("wares.lock");
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			let checks = doctor::diagnose(&manifest_file, &lock_file, &cache_dir, !(*offline || utils::offline_fallback()));
+			print_checks(&checks, *format);
+			if checks.iter().any(|check| check.status == Status::Fail) {
+				std::process::exit(1);
+			}
+		},
+		Command::Check { format, current } => {
+			let manifest_file = current.clone().unwrap_or(PathBuf::from("./")).join("wares.toml");
+
+			match check::check(&manifest_file) {
+				Ok(checks) => print_checks(&checks, *format),
+				Err(error) => fail(error)
+			}
+		},
+		Command::Cache { command, cache } => {
+			let cache_dir: PathBuf = cache.clone().unwrap_or_else(utils::cache_dir_fallback);
+
+			if let Err(error) = run_cache(command, &cache_dir) {
+				fail(error);
+			}
 		}
 	}
 }
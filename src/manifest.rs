@@ -1,14 +1,18 @@
 use std::collections::BTreeMap;
 use std::sync::OnceLock;
+use std::path::Path;
+use std::ops::Range;
+use std::fmt;
 
 // repositories
-use git2::Remote;
+use git2::{Remote, Repository};
 
 // regex
 use regex::Regex;
 
 // serialization/deserialization
-use toml::{Table, Value};
+use toml::{Table, Value, Spanned};
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
 
 // versioning
 use semver::{Version, VersionReq};
@@ -18,58 +22,163 @@ use snafu::{Snafu, ResultExt, Backtrace};
 
 // internal imports
 use crate::utils;
+use crate::forge;
+use crate::auth;
 
 use crate::lock::{LockedDependency, LockedDependencyId};
+use crate::cache::{CacheIndex, CachedObject};
+use crate::archive::{self, ArchiveFormat};
+use crate::patch::PatchSet;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Specifier {
 	MainBranch,
 	Branch(String),
 	CommitHash([u8; 20]), // hash value? 40 hexadecimal digits ==> array of u8
 	Tag(String),
 	Rev(String),
-	Version(VersionReq)
+	Version(VersionReq),
+	// the url is an archive instead of a repository, identified by the sha256 of the file (found when locking if it's missing)
+	Archive{ sha256: Option<String>, strip_components: usize },
+	// the url is a folder on the disk, relative to the manifest until ManifestFile::resolve_paths is called
+	Path
 }
 
-#[derive(Debug)]
+impl fmt::Display for Specifier {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Specifier::MainBranch => write!(f, "main branch"),
+			Specifier::Branch(branch) => write!(f, "branch {branch}"),
+			Specifier::CommitHash(hash) => write!(f, "commit {}", utils::format_hex::<20>(hash)),
+			Specifier::Tag(tag) => write!(f, "tag {tag}"),
+			Specifier::Rev(rev) => write!(f, "rev {rev}"),
+			Specifier::Version(requirement) => write!(f, "{requirement}"),
+			Specifier::Archive{ sha256: Some(sha256), .. } => write!(f, "archive sha256 {:.12}", sha256),
+			Specifier::Archive{ sha256: None, .. } => write!(f, "archive"),
+			Specifier::Path => write!(f, "path")
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
 pub struct ManifestDependency {
 	pub name: String,
 	repo_url: String,
 	specifier: Specifier,
+	// check out the repository's submodules too (recursively)
+	pub submodules: bool,
+	// patch files applied after the install (relative to the manifest until ManifestFile::resolve_paths is called)
+	pub patches: Vec<String>,
+	// the folder inside of the repository that holds the package
+	pub subdir: Option<String>,
+	// the only paths that are checked out (relative to the repository)
+	pub sparse: Vec<String>,
 	//premake_include: bool,
 	//cmake_include: bool,
 
 	// version: semver, // version or commit or revision or branch or tag
 }
 
+// errors in a single dependency, ManifestFileParseError::DependencyParse adds where it is in the manifest
 #[derive(Debug, Snafu)]
 pub enum DependencyParseError {
-	// todo: add group + context information
 	#[snafu(display("Missing the {key} key in dependency."))]
 	DepMissingKey{ key: &'static str },
 
-	// todo: add line + column information w/ context
 	#[snafu(display("The {key} key must of of type {required_type}."))]
 	DepWrongType{ key: &'static str, required_type: &'static str },
 
-	// todo: add line + column information w/ context
 	#[snafu(display("Missing dependency specifier. One of: version, commit, rev, branch or tag required"))]
 	MissingSpecifier,
 
-	// todo: add line + column information w/ context
 	#[snafu(display("Dependency type {provider_id} is unknown."))]
 	UnknownProvider{ provider_id: String },
 
-	// todo: print context + column information
 	#[snafu(display("Failed to parse the semantic verion: {source}"))]
 	SemverParse{ source: semver::Error },
 
-	// todo: print context + column information
 	#[snafu(display("Failed to parse the commit hash: {source}"))]
 	CommitParse{source: std::num::ParseIntError},
 
+	#[snafu(display("Expected a dependency type (gh:, git:, url:, path:, ...) at the start of {dependency}"))]
+	MissingProvider{ dependency: String },
+
 	#[snafu(display("Failed to parse the specifier from {specifier}"))]
-	SpecifierParseError{ specifier: String }
+	SpecifierParseError{ specifier: String },
+
+	#[snafu(display("Expected a username and repository (user/repository) in {dependency}"))]
+	RepositoryParse{ dependency: String },
+
+	#[snafu(display("Expected a git url ending in .git (https://, http://, ssh://, git://, file:// or user@host:path) in {dependency}"))]
+	GitUrlParse{ dependency: String },
+
+	#[snafu(display("The local dependency at {path} can't use {option}, it's used in place instead of being copied"))]
+	PathOption{ path: String, option: &'static str },
+
+	#[snafu(display("{path} isn't a path inside of the repository (it can't be absolute or contain ..)"))]
+	RepositoryPathParse{ path: String },
+
+	#[snafu(display("Failed to read the forges in the config file: {message}"))]
+	ForgeConfig{ message: String },
+
+	#[snafu(display("Can't tell the archive format of {url}, expected a .tar.gz, .tar.xz, .tar.bz2 or .zip file"))]
+	UnknownArchiveFormat{ url: String },
+
+	#[snafu(display("{value} isn't a sha256 hash (64 hexadecimal digits)"))]
+	Sha256Parse{ value: String },
+
+	#[snafu(display("Unknown key {key} for a dependency of type {dependency_type}"))]
+	UnknownKey{ key: String, dependency_type: String },
+
+	#[snafu(display("Only one of version, commit, rev, branch or tag can be given, found {}", keys.join(" and ")))]
+	ConflictingSpecifiers{ keys: Vec<&'static str> }
+}
+
+impl DependencyParseError {
+	// the key of a dependency table the error is about, if it's about one of them
+	fn key(&self) -> Option<&str> {
+		match self {
+			DependencyParseError::DepWrongType{ key, .. } => Some(key),
+			DependencyParseError::UnknownKey{ key, .. } => Some(key),
+			// the first one is fine on its own, the second is the one that conflicts
+			DependencyParseError::ConflictingSpecifiers{ keys } => keys.get(1).copied(),
+			DependencyParseError::UnknownProvider{ .. } => Some("type"),
+			DependencyParseError::SemverParse{ .. } => Some("version"),
+			DependencyParseError::CommitParse{ .. } => Some("commit"),
+			DependencyParseError::UnknownArchiveFormat{ .. } => Some("url"),
+			DependencyParseError::Sha256Parse{ .. } => Some("sha256"),
+			DependencyParseError::PathOption{ option, .. } => Some(option),
+			_ => None
+		}
+	}
+
+	// how the dependency could be written instead
+	fn hint(&self) -> Option<String> {
+		match self {
+			DependencyParseError::DepMissingKey{ key } => Some(format!("add {key} = \"...\" to the dependency's table")),
+			DependencyParseError::DepWrongType{ key, required_type } => Some(format!("{key} takes a {required_type}")),
+			DependencyParseError::UnknownProvider{ .. } | DependencyParseError::MissingProvider{ .. } =>
+				Some(String::from("dependencies start with their type: gh:, gl:, bb:, cb:, srht: (or a forge from the config file), git:, url: or path:")),
+			DependencyParseError::SemverParse{ .. } => Some(String::from("version requirements look like ^1.14, ~2.0, >=1.2, <2 or =1.0.3")),
+			DependencyParseError::CommitParse{ .. } => Some(String::from("commits are written out in full (40 hexadecimal digits)")),
+			DependencyParseError::SpecifierParseError{ .. } => Some(String::from("a specifier starts with @ (version), / (branch), ! (rev) or # (tag), i.e. gh:gabime/spdlog@^1.14")),
+			DependencyParseError::RepositoryParse{ .. } => Some(String::from("i.e. gh:gabime/spdlog or gh:gabime/spdlog@^1.14")),
+			DependencyParseError::GitUrlParse{ .. } => Some(String::from("i.e. git:https://github.com/gabime/spdlog.git@^1.14")),
+			DependencyParseError::PathOption{ option, .. } => Some(format!("remove {option}, or change the local copy itself")),
+			DependencyParseError::RepositoryPathParse{ .. } => Some(String::from("paths are relative to the root of the repository, i.e. include or src/core")),
+			DependencyParseError::UnknownKey{ key, dependency_type } => {
+				// a specifier on a dependency that can't have one is still worth naming
+				let known = keys_of(dependency_type);
+				let candidates: Vec<&str> = known.iter().chain(SPECIFIER_KEYS.iter()).copied().collect();
+				match closest(key, &candidates).filter(|suggestion| known.contains(suggestion)) {
+					Some(suggestion) => Some(format!("did you mean {suggestion}?")),
+					None => Some(format!("dependencies of type {dependency_type} take {}", known.join(", ")))
+				}
+			},
+			DependencyParseError::ConflictingSpecifiers{ .. } => Some(String::from("keep the one the dependency should follow and remove the others")),
+			_ => None
+		}
+	}
 }
 
 #[derive(Debug, Snafu)]
@@ -90,52 +199,169 @@ pub enum LockingError {
 	NoTag{ tag: String },
 
 	#[snafu(display("Failed to find the rev specified: {rev}"))]
-	NoRev{ rev: String }
+	NoRev{ rev: String },
+
+	#[snafu(display("Failed to read the cache index: {source}"))]
+	CacheIndex{
+		#[snafu(source(from(crate::SyncError, Box::new)))]
+		source: Box<crate::SyncError>
+	},
+
+	#[snafu(display("Failed to download the archive to find its hash: {source}"))]
+	ArchiveDownload{
+		#[snafu(source(from(crate::SyncError, Box::new)))]
+		source: Box<crate::SyncError>
+	},
+
+	#[snafu(display("Failed to read the patches: {source}"))]
+	PatchRead{
+		#[snafu(source(from(crate::SyncError, Box::new)))]
+		source: Box<crate::SyncError>
+	},
+
+	#[snafu(display("The local dependency at {path} can't be found: {source}"))]
+	PathNotFound{ path: String, source: std::io::Error },
+
+	#[snafu(display("The local dependency at {path} isn't a folder"))]
+	PathNotADirectory{ path: String },
+
+	#[snafu(display("Nothing has been cached from {url}, so it can't be resolved while offline"))]
+	NotCached{ url: String },
+
+	#[snafu(display("Nothing cached from {url} satisfies {specifier}, so it can't be resolved while offline"))]
+	OfflineNoMatch{ url: String, specifier: String }
+}
+
+// normalizes a path inside of a repository (foo\\bar/ => foo/bar), rejecting paths that could point outside of it
+fn repository_path(path: &str) -> Result<String, DependencyParseError> {
+	let parts: Vec<&str> = path.split(['/', '\\']).filter(|part| !part.is_empty() && *part != ".").collect();
+	if path.starts_with(['/', '\\']) || path.contains(':') || parts.is_empty() || parts.contains(&"..") {
+		return Err(DependencyParseError::RepositoryPathParse{ path: path.to_string() });
+	}
+	Ok(parts.join("/"))
+}
+
+// the keys each type of dependency table takes, anything else is rejected so typos don't go unnoticed
+pub(crate) const SPECIFIER_KEYS: [&str; 5] = ["version", "commit", "rev", "branch", "tag"];
+pub(crate) const OPTION_KEYS: [&str; 4] = ["submodules", "patches", "subdir", "sparse"];
+pub(crate) const GIT_KEYS: [&str; 2] = ["type", "url"];
+pub(crate) const FORGE_KEYS: [&str; 3] = ["type", "username", "repository"];
+pub(crate) const ARCHIVE_KEYS: [&str; 4] = ["type", "url", "sha256", "strip_components"];
+pub(crate) const PATH_KEYS: [&str; 2] = ["type", "path"];
+
+// every key a type of dependency takes
+fn keys_of(dependency_type: &str) -> Vec<&'static str> {
+	let (keys, specifiers): (&[&'static str], bool) = match dependency_type {
+		"git" => (&GIT_KEYS, true),
+		"archive" | "url" | "zip" => (&ARCHIVE_KEYS, false),
+		"path" => (&PATH_KEYS, false),
+		_ => (&FORGE_KEYS, true)
+	};
+
+	let mut all = keys.to_vec();
+	if specifiers {
+		all.extend(SPECIFIER_KEYS);
+	}
+	all.extend(OPTION_KEYS);
+	all
+}
+
+// the number of single character edits between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut previous: Vec<usize> = (0..=b.len()).collect();
+	for (i, a) in a.chars().enumerate() {
+		let mut current = vec![i + 1];
+		for (j, b) in b.iter().enumerate() {
+			current.push((previous[j] + usize::from(a != *b)).min(previous[j + 1] + 1).min(current[j] + 1));
+		}
+		previous = current;
+	}
+	previous[b.len()]
+}
+
+// the candidate closest to a misspelled key, if one is close enough to be what was meant
+pub(crate) fn closest<'a>(key: &str, candidates: &[&'a str]) -> Option<&'a str> {
+	candidates.iter()
+		.map(|candidate| (edit_distance(key, candidate), *candidate))
+		.filter(|(distance, candidate)| *distance <= 2 && *distance < candidate.len())
+		.min()
+		.map(|(_, candidate)| candidate)
+}
+
+// rejects the keys a type of dependency doesn't take, and giving more than one specifier
+fn check_keys(dep_table: &toml::map::Map<String, Value>, dependency_type: &str) -> Result<(), DependencyParseError> {
+	let known = keys_of(dependency_type);
+	if let Some(key) = dep_table.keys().find(|key| !known.contains(&key.as_str())) {
+		return Err(DependencyParseError::UnknownKey{ key: key.clone(), dependency_type: dependency_type.to_string() });
+	}
+
+	let specifiers: Vec<&'static str> = SPECIFIER_KEYS.into_iter().filter(|key| dep_table.contains_key(*key)).collect();
+	if specifiers.len() > 1 {
+		return Err(DependencyParseError::ConflictingSpecifiers{ keys: specifiers });
+	}
+
+	Ok(())
 }
 
 impl ManifestDependency {
+	pub fn new(name: String, repo_url: String, specifier: Specifier) -> ManifestDependency {
+		ManifestDependency { name: name, repo_url: repo_url, specifier: specifier, submodules: false, patches: Vec::new(), subdir: None, sparse: Vec::new() }
+	}
+
 	fn unnamed(repo_url: String, specifier: Specifier) -> ManifestDependency {
-		ManifestDependency { name: String::from(""), repo_url: repo_url, specifier: specifier }
+		ManifestDependency::new(String::from(""), repo_url, specifier)
 	}
 
-	fn parse(toml_value: &Value) -> Result<ManifestDependency, DependencyParseError> {
+	pub fn parse(toml_value: &Value) -> Result<ManifestDependency, DependencyParseError> {
 		match toml_value {
 			Value::String(dep_str) => {
 				fn parse_specifier(end_of_dep: &str) -> Result<Specifier, DependencyParseError> {
-					if end_of_dep.is_empty() {
+					let mut chars = end_of_dep.chars();
+					let Some(kind) = chars.next() else {
 						return Ok(Specifier::MainBranch)
-					}
+					};
 
-					match &end_of_dep[..1] {
-						"@" => Ok(Specifier::Version(
-							VersionReq::parse(&end_of_dep[1..]).context(SemverParseSnafu{})?
+					let value = chars.as_str();
+					match kind {
+						'@' => Ok(Specifier::Version(
+							VersionReq::parse(value).context(SemverParseSnafu{})?
 						)),
-						"/" => Ok(Specifier::Branch(end_of_dep[1..].to_string())),
-						"!" => Ok(Specifier::Rev(end_of_dep[1..].to_string())),
-						"#" => Ok(Specifier::Tag(end_of_dep[1..].to_string())),
+						'/' => Ok(Specifier::Branch(value.to_string())),
+						'!' => Ok(Specifier::Rev(value.to_string())),
+						'#' => Ok(Specifier::Tag(value.to_string())),
 						_ => Err(DependencyParseError::SpecifierParseError{specifier: end_of_dep.to_string()})
 					}
 				}
 
-				let type_end = dep_str.find(":").expect("Missing semicolon in dependency string");
+				let type_end = dep_str.find(":").ok_or_else(|| DependencyParseError::MissingProvider{ dependency: dep_str.clone() })?;
 				let dep_type = &dep_str[0..type_end];
 
 				static USERNAME_REPOSITORY_REGEX: OnceLock<Regex> = OnceLock::new();
-				let username_repository_regex = USERNAME_REPOSITORY_REGEX.get_or_init(|| Regex::new(r"[\w-]+/[\w-]+").unwrap());
-
-				let (url, specifier) = if dep_type == "gh" || dep_type == "github" {
-					let username_repository = username_repository_regex.find(&dep_str[type_end..]).expect("Failed to parse github info");
-					let specifier = parse_specifier(&dep_str[(type_end + username_repository.end())..])?;
-					(format!("https://github.com/{}.git", username_repository.as_str()), specifier)
-				} else if dep_type == "gl" || dep_type == "gitlab" {
-					let username_repository = username_repository_regex.find(&dep_str[type_end..]).expect("Failed to parse gitlab info");
-					let specifier = parse_specifier(&dep_str[(type_end + username_repository.end())..])?;
-					(format!("https://gitlab.com/{}.git", username_repository.as_str()), specifier)
+				let username_repository_regex = USERNAME_REPOSITORY_REGEX.get_or_init(|| Regex::new(r"^([\w-]+)/([\w.-]+?)(?:$|[@/!#])").unwrap());
+
+				let (url, specifier) = if let Some(forge) = forge::find(dep_type).map_err(|message| DependencyParseError::ForgeConfig{ message: message.to_string() })? {
+					// gh:user/repository followed by an optional specifier
+					let rest = &dep_str[(type_end + 1)..];
+					let username_repository = username_repository_regex.captures(rest).ok_or_else(|| DependencyParseError::RepositoryParse{ dependency: dep_str.clone() })?;
+					let specifier = parse_specifier(&rest[username_repository[1].len() + 1 + username_repository[2].len()..])?;
+					(forge.url(&username_repository[1], &username_repository[2]), specifier)
+				} else if dep_type == "url" || dep_type == "zip" {
+					// everything after the prefix is the url, archives don't have a specifier
+					let url = dep_str[(type_end + 1)..].trim().to_string();
+					if ArchiveFormat::from_url(&url).is_none() {
+						return Err(DependencyParseError::UnknownArchiveFormat{ url });
+					}
+					(url, Specifier::Archive{ sha256: None, strip_components: 0 })
+				} else if dep_type == "path" {
+					(dep_str[(type_end + 1)..].trim().to_string(), Specifier::Path)
 				} else if dep_type == "git" {
+					// https://, http://, ssh://, git:// and file:// urls, or scp-like ssh urls (git@github.com:gabime/spdlog.git)
 					static GIT_REGEX: OnceLock<Regex> = OnceLock::new();
-					let git_regex = GIT_REGEX.get_or_init(|| Regex::new(r"https://[\w\.@\:/\-~]+.git").unwrap());
-					let url_match = git_regex.find(&dep_str[type_end..]).expect("Failed to find git url");
-					let specifier = parse_specifier(&dep_str[(type_end + url_match.end())..])?;
+					let git_regex = GIT_REGEX.get_or_init(|| Regex::new(r"^((?:(?:https?|ssh|git|file)://|[\w.-]+@[\w.-]+:)[\w.@:/~%+-]*?\.git)(?:$|[@/!#])").unwrap());
+					let rest = &dep_str[(type_end + 1)..];
+					let url_match = git_regex.captures(rest).and_then(|captures| captures.get(1)).ok_or_else(|| DependencyParseError::GitUrlParse{ dependency: dep_str.clone() })?;
+					let specifier = parse_specifier(&rest[url_match.end()..])?;
 					(url_match.as_str().to_string(), specifier)
 				} else {
 					return Err(DependencyParseError::UnknownProvider{ provider_id: dep_type.to_string() })
@@ -152,12 +378,52 @@ impl ManifestDependency {
 				}
 
 				let dep_type: &str = get_str(&dep_table, "type")?;
+
+				if matches!(dep_type, "archive" | "url" | "zip") {
+					check_keys(dep_table, dep_type)?;
+					let url = get_str(&dep_table, "url")?.to_string();
+					if ArchiveFormat::from_url(&url).is_none() {
+						return Err(DependencyParseError::UnknownArchiveFormat{ url });
+					}
+
+					let sha256 = match dep_table.get("sha256") {
+						Some(_) => {
+							let value = get_str(&dep_table, "sha256")?.to_lowercase();
+							if !utils::is_sha256(&value) {
+								return Err(DependencyParseError::Sha256Parse{ value });
+							}
+							Some(value)
+						},
+						None => None
+					};
+
+					let strip_components = match dep_table.get("strip_components") {
+						Some(value) => value.as_integer()
+							.and_then(|value| usize::try_from(value).ok())
+							.ok_or(DependencyParseError::DepWrongType{ key: "strip_components", required_type: "non-negative integer" })?,
+						None => 0
+					};
+
+					return ManifestDependency::unnamed(url, Specifier::Archive{ sha256, strip_components }).parse_options(dep_table);
+				}
+
+				if dep_type == "path" {
+					check_keys(dep_table, dep_type)?;
+					return ManifestDependency::unnamed(get_str(&dep_table, "path")?.to_string(), Specifier::Path).parse_options(dep_table);
+				}
 				
-				let repo_url: String = match dep_type {
-					"git" => String::from(get_str(&dep_table, "url")?),
-					"github" | "gh" => format!("https://github.com/{}/{}.git", get_str(&dep_table, "username")?, get_str(&dep_table, "repository")?),
-					"gitlab" | "gl" => format!("https://gitlab.com/{}/{}.git", get_str(&dep_table, "username")?, get_str(&dep_table, "repository")?),
-					_ => return Err(DependencyParseError::UnknownProvider{ provider_id: dep_type.to_string() }) // we don't know this one
+				let forge = match dep_type {
+					"git" => None,
+					_ => match forge::find(dep_type).map_err(|message| DependencyParseError::ForgeConfig{ message: message.to_string() })? {
+						Some(forge) => Some(forge),
+						None => return Err(DependencyParseError::UnknownProvider{ provider_id: dep_type.to_string() }) // we don't know this one
+					}
+				};
+				check_keys(dep_table, dep_type)?;
+
+				let repo_url: String = match forge {
+					None => String::from(get_str(&dep_table, "url")?),
+					Some(forge) => forge.url(get_str(&dep_table, "username")?, get_str(&dep_table, "repository")?)
 				};
 
 				let specifier = if dep_table.contains_key("version") {
@@ -171,14 +437,14 @@ impl ManifestDependency {
 				} else if dep_table.contains_key("rev") {
 					Specifier::Rev(get_str(&dep_table, "rev")?.to_string())
 				} else if dep_table.contains_key("branch") {
-					Specifier::Branch(get_str(&dep_table, "rev")?.to_string())
+					Specifier::Branch(get_str(&dep_table, "branch")?.to_string())
 				} else if dep_table.contains_key("tag") {
-					Specifier::Tag(get_str(&dep_table, "rev")?.to_string())
+					Specifier::Tag(get_str(&dep_table, "tag")?.to_string())
 				} else {
 					Specifier::MainBranch // main branch, echo warning?
 				};
 
-				Ok(ManifestDependency::unnamed(repo_url, specifier))
+				ManifestDependency::unnamed(repo_url, specifier).parse_options(dep_table)
 			},
 			_ => {
 				Err(DependencyParseError::DepWrongType { key: "type", required_type: "string or table" })
@@ -186,108 +452,313 @@ impl ManifestDependency {
 		}
 	}
 
-	pub fn lock(&self) -> Result<LockedDependency, LockingError> {
-		match &self.specifier {
-			Specifier::MainBranch => Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::MainBranch)), // TODO: update to actual choose the default branch
-			Specifier::Branch(branch) => Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Branch(branch.clone()))),
-			Specifier::Version(requirement) => {
-				// git ls-remote
-				// parse the remote refs for version information
-				// i wish i could put this in a function
-				let mut remote = Remote::create_detached(self.repo_url.clone()).context(GitSnafu{})?;
-				remote.connect(git2::Direction::Fetch).context(GitSnafu{})?;
-				let refs = remote.list().context(GitSnafu{})?;
+	// reads the options that change how a dependency is installed from its table
+	fn parse_options(mut self, dep_table: &toml::map::Map<String, Value>) -> Result<ManifestDependency, DependencyParseError> {
+		if let Some(submodules) = dep_table.get("submodules") {
+			self.submodules = submodules.as_bool().ok_or(DependencyParseError::DepWrongType{ key: "submodules", required_type: "boolean" })?;
+		}
 
-				let mut versions: BTreeMap<Version, git2::Oid> = BTreeMap::new();
+		fn get_strings(value: &Value, key: &'static str) -> Result<Vec<String>, DependencyParseError> {
+			value.as_array()
+				.and_then(|values| values.iter().map(|value| value.as_str().map(String::from)).collect::<Option<Vec<String>>>())
+				.ok_or(DependencyParseError::DepWrongType{ key, required_type: "array of strings" })
+		}
 
-				// this is one girthy regex! recommend regex101 or other regex validator
-				static VERSION_REGEX: OnceLock<Regex> = OnceLock::new();
-				let version_regex = VERSION_REGEX.get_or_init(|| Regex::new(r"refs/tags/v?((?:0|[1-9]\d*)(?:\.(?:0|[1-9]\d*))?(?:\.(?:0|[1-9]\d*))?(?:-(?:(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?(?:\+(?:[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?)").unwrap());
-				
-				for git_ref in refs {
-					let potential_version_str = git_ref.name();
-					match version_regex.captures(potential_version_str) {
-						Some(captures) => {
-							// stop gap solution to try fixing the version requirement by adding .0 (0 to 2 times)
-							let mut version = String::from(&captures[1]);
-
-							let version_groups = captures[1].chars().filter(|x| *x == '.').count() + 1;
-							for i in version_groups..3 {
-								version += ".0";
-							}
+		if let Some(patches) = dep_table.get("patches") {
+			self.patches = get_strings(patches, "patches")?;
+		}
 
-							versions.insert(Version::parse(&version).context(VersionSnafu)?, git_ref.oid());
-						},
-						_ => {}
-					}
-				}
+		if let Some(subdir) = dep_table.get("subdir") {
+			let subdir = subdir.as_str().ok_or(DependencyParseError::DepWrongType{ key: "subdir", required_type: "string" })?;
+			self.subdir = Some(repository_path(subdir)?);
+		}
+
+		if let Some(sparse) = dep_table.get("sparse") {
+			self.sparse = get_strings(sparse, "sparse")?.iter().map(|path| repository_path(path)).collect::<Result<Vec<String>, DependencyParseError>>()?;
+		}
+
+		// local folders are used in place, so there's no copy to patch or check out partially
+		if let Specifier::Path = self.specifier {
+			if !self.patches.is_empty() {
+				return Err(DependencyParseError::PathOption{ path: self.repo_url.clone(), option: "patches" });
+			}
+			if !self.sparse.is_empty() {
+				return Err(DependencyParseError::PathOption{ path: self.repo_url.clone(), option: "sparse" });
+			}
+		}
+
+		Ok(self)
+	}
 
-				// extract the latest version that matches the requirements from the versions map
-				let mut oid = git2::Oid::zero();
+	// makes the path of a local dependency and its patches relative to the folder the manifest is in
+	pub fn resolve_paths(&mut self, folder: &Path) {
+		if let Specifier::Path = self.specifier {
+			self.repo_url = folder.join(&self.repo_url).to_string_lossy().to_string();
+		}
+
+		for patch in self.patches.iter_mut() {
+			*patch = folder.join(&*patch).to_string_lossy().to_string();
+		}
+	}
 
-				for version in versions.keys().rev() {
-					// find the latest match
-					if requirement.matches(version) {
-						oid = versions[version];
-						break;
+	pub fn repo_url(&self) -> &str {
+		&self.repo_url
+	}
+
+	pub fn specifier(&self) -> &Specifier {
+		&self.specifier
+	}
+
+	// lists every version tagged in the repository
+	pub fn versions(&self, refs: RefSource) -> Result<BTreeMap<Version, RemoteRef>, LockingError> {
+		let mut versions: BTreeMap<Version, RemoteRef> = BTreeMap::new();
+
+		// this is one girthy regex! recommend regex101 or other regex validator
+		static VERSION_REGEX: OnceLock<Regex> = OnceLock::new();
+		let version_regex = VERSION_REGEX.get_or_init(|| Regex::new(r"refs/tags/v?((?:0|[1-9]\d*)(?:\.(?:0|[1-9]\d*))?(?:\.(?:0|[1-9]\d*))?(?:-(?:(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*)(?:\.(?:0|[1-9]\d*|\d*[a-zA-Z-][0-9a-zA-Z-]*))*))?(?:\+(?:[0-9a-zA-Z-]+(?:\.[0-9a-zA-Z-]+)*))?)").unwrap());
+		
+		for git_ref in refs.list(&self.repo_url)? {
+			match version_regex.captures(&git_ref.name) {
+				Some(captures) => {
+					// stop gap solution to try fixing the version requirement by adding .0 (0 to 2 times)
+					let mut version = String::from(&captures[1]);
+
+					let version_groups = captures[1].chars().filter(|x| *x == '.').count() + 1;
+					for i in version_groups..3 {
+						version += ".0";
 					}
-				}
 
-				if oid.is_zero() {
-					return Err(LockingError::NoMatch{ requirement: requirement.to_string()});
+					versions.insert(Version::parse(&version).context(VersionSnafu)?, git_ref);
+				},
+				_ => {}
+			}
+		}
+
+		Ok(versions)
+	}
+
+	// picks out the versions that match a version requirement, newest first
+	// other specifiers don't have a version, so they never match
+	pub fn matching_versions(&self, versions: &BTreeMap<Version, RemoteRef>) -> Vec<LockedDependency> {
+		let Specifier::Version(requirement) = &self.specifier else {
+			return vec![];
+		};
+
+		versions.iter()
+			.rev()
+			.filter(|(version, _)| requirement.matches(version))
+			.map(|(version, git_ref)| {
+				let mut locked = LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(git_ref.oid));
+				locked.version = Some(version.clone());
+				locked.reference = Some(git_ref.name.clone());
+				locked
+			})
+			.collect()
+	}
+
+	// every locked dependency that satisfies this dependency, in order of preference
+	pub fn candidates(&self, refs: RefSource) -> Result<Vec<LockedDependency>, LockingError> {
+		let candidates = match &self.specifier {
+			Specifier::Version(requirement) => {
+				let candidates = self.matching_versions(&self.versions(refs)?);
+				if candidates.is_empty() && refs.is_offline() {
+					return Err(self.not_found(refs, LockingError::NoMatch{ requirement: requirement.to_string() }));
 				}
+				candidates
+			},
+			_ => vec![self.lock_source(refs)?]
+		};
+
+		candidates.into_iter().map(|candidate| self.with_options(candidate)).collect()
+	}
+
+	// adds the install options from the manifest to a locked dependency
+	fn with_options(&self, mut locked: LockedDependency) -> Result<LockedDependency, LockingError> {
+		if self.submodules {
+			// the commits are filled in once the submodules are checked out
+			locked.submodules = Some(BTreeMap::new());
+		}
+
+		if !self.patches.is_empty() {
+			locked.patches = Some(PatchSet::new(self.patches.clone()).context(PatchReadSnafu)?);
+		}
+
+		locked.subdir = self.subdir.clone();
+		if !self.sparse.is_empty() {
+			locked.sparse = Some(self.sparse.clone());
+		}
+
+		Ok(locked)
+	}
+
+	// when offline, failing to find something only means that it isn't in the cache
+	fn not_found(&self, refs: RefSource, error: LockingError) -> LockingError {
+		if refs.is_offline() {
+			LockingError::OfflineNoMatch{ url: self.repo_url.clone(), specifier: self.specifier.to_string() }
+		} else {
+			error
+		}
+	}
+
+	pub fn lock(&self, refs: RefSource) -> Result<LockedDependency, LockingError> {
+		self.with_options(self.lock_source(refs)?)
+	}
 
-				Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(oid)))
+	// locks the commit/branch/archive/folder the dependency comes from
+	fn lock_source(&self, refs: RefSource) -> Result<LockedDependency, LockingError> {
+		match &self.specifier {
+			Specifier::MainBranch => Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::MainBranch)), // TODO: update to actual choose the default branch
+			Specifier::Branch(branch) => Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Branch(branch.clone()))),
+			Specifier::Version(requirement) => {
+				// the newest version that matches is the first candidate
+				self.candidates(refs)?.into_iter().next().ok_or(LockingError::NoMatch{ requirement: requirement.to_string() })
 			},
 			Specifier::Tag(tag) => {
-				// i wish i could put this in a function
-				let mut remote = Remote::create_detached(self.repo_url.clone()).context(GitSnafu)?;
-				remote.connect(git2::Direction::Fetch).context(GitSnafu)?;
-				let refs = remote.list().context(GitSnafu)?;
-
-				let mut oid = git2::Oid::zero();
 				// matches refs/tags/...
 				let tag_regex = Regex::new(&format!("refs/tags/{}", &tag)).context(RegexSnafu)?;
-				for git_ref in refs {
-					match tag_regex.captures(git_ref.name()) {
-						Some(captures) => {
-							oid = git_ref.oid();
-							break;
-						},
-						_ => {}
-					}
+				let git_ref = refs.list(&self.repo_url)?.into_iter()
+					.find(|git_ref| tag_regex.is_match(&git_ref.name))
+					.ok_or_else(|| self.not_found(refs, LockingError::NoTag{ tag: tag.clone() }))?;
+
+				let mut locked = LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(git_ref.oid));
+				locked.reference = Some(git_ref.name);
+				Ok(locked)
+			},
+			Specifier::Rev(rev) => {
+				let git_ref = refs.list(&self.repo_url)?.into_iter()
+					.find(|git_ref| rev == &git_ref.name)
+					.ok_or_else(|| self.not_found(refs, LockingError::NoRev{ rev: rev.clone() }))?;
+
+				let mut locked = LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(git_ref.oid));
+				locked.reference = Some(git_ref.name);
+				Ok(locked)
+			}
+			Specifier::CommitHash(hash) => {
+				Ok(LockedDependency::new(self.repo_url.clone(), 
+									 LockedDependencyId::Oid(git2::Oid::from_bytes(hash).context(GitSnafu)?))) // this is a big line!
+			},
+			Specifier::Path => {
+				// the same folder has to end up with the same path, no matter how it was reached
+				let path = utils::get_full_path(&self.repo_url).context(PathNotFoundSnafu{ path: self.repo_url.clone() })?;
+				if !path.is_dir() {
+					return Err(LockingError::PathNotADirectory{ path: path.display().to_string() });
 				}
 
-				if oid.is_zero() {
-					return Err(LockingError::NoTag{ tag: tag.clone() })
+				Ok(LockedDependency::new(path.to_string_lossy().to_string(), LockedDependencyId::Path))
+			},
+			Specifier::Archive{ sha256, strip_components } => {
+				// without a hash in the manifest, the archive has to be downloaded to find out what it is
+				let sha256 = match sha256 {
+					Some(sha256) => sha256.clone(),
+					None => refs.archive_hash(&self.repo_url)?
+				};
+
+				Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Archive{ sha256, strip_components: *strip_components }))
+			}
+		}
+	}
+}
+
+// a ref advertised by a repository, tags are always peeled to the commit they point at
+#[derive(Clone, Debug)]
+pub struct RemoteRef {
+	pub name: String,
+	pub oid: git2::Oid
+}
+
+// where the refs of a repository are looked up
+#[derive(Clone, Copy, Debug)]
+pub enum RefSource<'a> {
+	// ask the remote repository (git ls-remote)
+	Remote,
+	// only look at the repositories that are already in the cache folder
+	Cache(&'a Path)
+}
+
+impl RefSource<'_> {
+	pub fn is_offline(&self) -> bool {
+		matches!(self, RefSource::Cache(_))
+	}
+
+	// finds the sha256 of the archive at url
+	pub fn archive_hash(&self, url: &str) -> Result<String, LockingError> {
+		match self {
+			RefSource::Remote => archive::download(url).context(ArchiveDownloadSnafu),
+			RefSource::Cache(cache_folder) => {
+				let index = CacheIndex::load(cache_folder).context(CacheIndexSnafu)?;
+				let sha256 = index.installs_of(url).find_map(|install| match &install.object {
+					CachedObject::Archive(sha256) => Some(utils::format_hex(sha256)),
+					_ => None
+				});
+
+				sha256.ok_or_else(|| LockingError::NotCached{ url: url.to_string() })
+			}
+		}
+	}
+
+	// lists the refs of the repository at url
+	pub fn list(&self, url: &str) -> Result<Vec<RemoteRef>, LockingError> {
+		match self {
+			RefSource::Remote => {
+				// git ls-remote
+				let mut remote = Remote::create_detached(url).context(GitSnafu)?;
+				remote.connect_auth(git2::Direction::Fetch, Some(auth::callbacks()), None).context(GitSnafu)?;
+
+				let mut refs: Vec<RemoteRef> = Vec::new();
+				for head in remote.list().context(GitSnafu)? {
+					// annotated tags are listed twice, the second time (with a ^{} suffix) peeled to their commit
+					match head.name().strip_suffix("^{}") {
+						Some(tag_name) => {
+							if let Some(tag) = refs.iter_mut().find(|git_ref| git_ref.name == tag_name) {
+								tag.oid = head.oid();
+							}
+						},
+						None => refs.push(RemoteRef{ name: head.name().to_string(), oid: head.oid() })
+					}
 				}
 
-				Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(oid)))
+				Ok(refs)
 			},
-			Specifier::Rev(rev) => {
-				// i wish i could put this in a function
-				let mut remote = Remote::create_detached(self.repo_url.clone()).context(GitSnafu)?;
-				remote.connect(git2::Direction::Fetch).context(GitSnafu)?;
-				let refs = remote.list().context(GitSnafu)?;
+			RefSource::Cache(cache_folder) => {
+				let mut refs: BTreeMap<String, git2::Oid> = BTreeMap::new();
+				let mut cached = false;
+
+				// every checkout in the cache that was installed from this url contributes its refs
+				let index = CacheIndex::load(cache_folder).context(CacheIndexSnafu)?;
+				for install in index.installs_of(url) {
+					let Ok(repository) = Repository::open(cache_folder.join(&install.folder)) else {
+						continue;
+					};
+					cached = true;
+
+					for reference in repository.references().context(GitSnafu)?.flatten() {
+						let Some(name) = reference.name() else {
+							continue;
+						};
 
-				let mut oid = git2::Oid::zero();
+						// remote tracking branches stand in for the branches of the remote, local branches are our own
+						let name = if let Some(branch) = name.strip_prefix("refs/remotes/origin/") {
+							if branch == "HEAD" {
+								continue;
+							}
+							format!("refs/heads/{branch}")
+						} else if name.starts_with("refs/heads/") || name.starts_with("refs/remotes/") {
+							continue;
+						} else {
+							name.to_string()
+						};
 
-				for git_ref in refs {
-					if rev == git_ref.name() {
-						oid = git_ref.oid();
-						break;
+						if let Ok(commit) = reference.peel_to_commit() {
+							refs.insert(name, commit.id());
+						}
 					}
 				}
 
-				if oid.is_zero() {
-					return Err(LockingError::NoRev{ rev: rev.clone() });
+				if !cached {
+					return Err(LockingError::NotCached{ url: url.to_string() });
 				}
 
-				Ok(LockedDependency::new(self.repo_url.clone(), LockedDependencyId::Oid(oid)))
-			}
-			Specifier::CommitHash(hash) => {
-				Ok(LockedDependency::new(self.repo_url.clone(), 
-									 LockedDependencyId::Oid(git2::Oid::from_bytes(hash).context(GitSnafu)?))) // this is a big line!
+				Ok(refs.into_iter().map(|(name, oid)| RemoteRef{ name: name, oid: oid }).collect())
 			}
 		}
 	}
@@ -305,13 +776,164 @@ pub enum ManifestFileParseError {
 	ManifestMissingKey{ key: &'static str },
 
 	#[snafu(display("Wrong type for key: {key}"))]
-	ManifestWrongType{ key: String },
+	ManifestWrongType{ key: String, span: Option<Range<usize>> },
+
+	#[snafu(display("Unknown group: {group}"))]
+	UnknownGroup{ group: String, span: Option<Range<usize>> },
 
 	#[snafu(display("TOML parse error: {source}"))]
 	TOML{ source: toml::de::Error },
 
-	#[snafu(display("Dependency parse error: {source}"))]
-	DependencyParse{ source: DependencyParseError }
+	#[snafu(display("Dependency parse error in {name} ([{group}]): {source}"))]
+	DependencyParse{ group: String, name: String, span: Option<Range<usize>>, source: DependencyParseError }
+}
+
+impl ManifestFileParseError {
+	// where in the manifest the error is, as a byte range
+	pub fn span(&self) -> Option<Range<usize>> {
+		match self {
+			ManifestFileParseError::ManifestMissingKey{ .. } => None,
+			ManifestFileParseError::ManifestWrongType{ span, .. } => span.clone(),
+			ManifestFileParseError::UnknownGroup{ span, .. } => span.clone(),
+			ManifestFileParseError::TOML{ source } => source.span(),
+			ManifestFileParseError::DependencyParse{ span, .. } => span.clone()
+		}
+	}
+
+	pub fn hint(&self) -> Option<String> {
+		match self {
+			ManifestFileParseError::ManifestMissingKey{ key } => Some(format!("add {key} = 1 to the top of the file")),
+			ManifestFileParseError::ManifestWrongType{ key, .. } if key == "manifest_version" => Some(String::from("the manifest version is a number, i.e. manifest_version = 1")),
+			ManifestFileParseError::ManifestWrongType{ key, .. } => Some(format!("every key other than manifest_version is a group of dependencies, i.e. [{key}]")),
+			ManifestFileParseError::UnknownGroup{ group, .. } => match closest(group, &["manifest_version", "dependencies", "dev-dependencies"]) {
+				Some(suggestion) => Some(format!("did you mean {suggestion}?")),
+				None => Some(String::from("groups are called dependencies or end in -dependencies, i.e. [dev-dependencies]"))
+			},
+			ManifestFileParseError::TOML{ .. } => None,
+			ManifestFileParseError::DependencyParse{ source, .. } => source.hint()
+		}
+	}
+
+	// renders the error like rustc does: the message, where it is, the line it's on with the span underlined
+	// and a hint
+	pub fn render(&self, file: &str, contents: &str) -> String {
+		let message = match self {
+			// toml's own display already has the line in it
+			ManifestFileParseError::TOML{ source } => format!("TOML parse error: {}", source.message()),
+			_ => self.to_string()
+		};
+
+		let mut rendered = format!("error: {message}\n");
+		let Some(span) = self.span().filter(|span| span.start <= contents.len()) else {
+			rendered += &format!(" --> {file}\n");
+			if let Some(hint) = self.hint() {
+				rendered += &format!("  = hint: {hint}\n");
+			}
+			return rendered;
+		};
+
+		let start = span.start;
+		let line_start = contents[..start].rfind('\n').map(|index| index + 1).unwrap_or(0);
+		let line_end = contents[start..].find('\n').map(|index| start + index).unwrap_or(contents.len());
+		let line = contents[..start].matches('\n').count() + 1;
+		let column = contents[line_start..start].chars().count() + 1;
+
+		// spans over several lines are only underlined up to the end of the first one
+		let end = span.end.clamp(start, line_end);
+		let text = contents[line_start..line_end].trim_end_matches('\r');
+		let indent: String = contents[line_start..start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+		let carets = "^".repeat(contents[start..end].chars().count().max(1));
+
+		let gutter = " ".repeat(line.to_string().len());
+		rendered += &format!("{gutter}--> {file}:{line}:{column}\n");
+		rendered += &format!("{gutter} |\n");
+		rendered += &format!("{line} | {text}\n");
+		rendered += &format!("{gutter} | {indent}{carets}\n");
+		if let Some(hint) = self.hint() {
+			rendered += &format!("{gutter} = hint: {hint}\n");
+		}
+		rendered
+	}
+}
+
+// the names a group of dependencies can have: dependencies (always installed) and anything ending in
+// -dependencies (dev-dependencies, desktop-dependencies, ...)
+pub fn is_group_name(name: &str) -> bool {
+	name == "dependencies" || (name.len() > "-dependencies".len() && name.ends_with("-dependencies"))
+}
+
+// the shape of a toml document, with where each value is, so errors can point at the value they're about.
+// only tables are kept, everything else is skipped over
+enum Located {
+	Table(BTreeMap<String, Spanned<Located>>),
+	Other
+}
+
+impl<'de> Deserialize<'de> for Located {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Located, D::Error> {
+		struct LocatedVisitor;
+
+		impl<'de> Visitor<'de> for LocatedVisitor {
+			type Value = Located;
+
+			fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+				formatter.write_str("a toml value")
+			}
+
+			fn visit_bool<E: de::Error>(self, _: bool) -> Result<Located, E> {
+				Ok(Located::Other)
+			}
+
+			fn visit_i64<E: de::Error>(self, _: i64) -> Result<Located, E> {
+				Ok(Located::Other)
+			}
+
+			fn visit_f64<E: de::Error>(self, _: f64) -> Result<Located, E> {
+				Ok(Located::Other)
+			}
+
+			fn visit_str<E: de::Error>(self, _: &str) -> Result<Located, E> {
+				Ok(Located::Other)
+			}
+
+			fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Located, A::Error> {
+				while seq.next_element::<IgnoredAny>()?.is_some() {}
+				Ok(Located::Other)
+			}
+
+			fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Located, A::Error> {
+				let mut table = BTreeMap::new();
+				while let Some(key) = map.next_key::<String>()? {
+					// toml hands dates out as a map with a single private key
+					if key == "$__toml_private_datetime" {
+						map.next_value::<IgnoredAny>()?;
+						return Ok(Located::Other);
+					}
+
+					table.insert(key, map.next_value()?);
+				}
+				Ok(Located::Table(table))
+			}
+		}
+
+		deserializer.deserialize_any(LocatedVisitor)
+	}
+}
+
+// the span of the value at a path of keys, or of the deepest one of them that's there
+fn span_of(info: &str, keys: &[&str]) -> Option<Range<usize>> {
+	let document: BTreeMap<String, Spanned<Located>> = toml::from_str(info).ok()?;
+	let (first, rest) = keys.split_first()?;
+
+	let mut value = document.get(*first)?;
+	for key in rest {
+		match value.get_ref() {
+			Located::Table(table) if table.contains_key(*key) => value = &table[*key],
+			_ => break
+		}
+	}
+
+	Some(value.span())
 }
 
 impl ManifestFile {
@@ -322,18 +944,27 @@ impl ManifestFile {
 		let mut manifest = ManifestFile { 
 			manifest_version: toml_table.get("manifest_version")
 										.ok_or(ManifestFileParseError::ManifestMissingKey{ key: "manifest_version" })?
-										.as_integer().ok_or(ManifestFileParseError::ManifestWrongType{ key: "manifest_version".to_string() })?, 
+										.as_integer().ok_or_else(|| ManifestFileParseError::ManifestWrongType{ key: "manifest_version".to_string(), span: span_of(info, &["manifest_version"]) })?, 
 			dependencies: BTreeMap::new() 
 		};
 
 		// fill out dependencies
 		for key in toml_table.keys().filter(|key| *key != "manifest_version") {
+			if !is_group_name(key) {
+				return Err(ManifestFileParseError::UnknownGroup{ group: key.clone(), span: span_of(info, &[key]) });
+			}
+
 			match &toml_table[key] {
 				Value::Table(deps) => {
 					let mut dependency_group: Vec<ManifestDependency> = Vec::new();
 					
 					for (dep_name, dep_spec) in deps {
-						let mut dep = ManifestDependency::parse(dep_spec).context(DependencyParseSnafu)?;
+						let mut dep = ManifestDependency::parse(dep_spec).with_context(|error| {
+							// the spans are only worked out when something is wrong
+							let mut keys = vec![key.as_str(), dep_name.as_str()];
+							keys.extend(error.key());
+							DependencyParseSnafu{ group: key.clone(), name: dep_name.clone(), span: span_of(info, &keys) }
+						})?;
 						dep.name = dep_name.clone();
 						dependency_group.push(dep);
 					}
@@ -342,7 +973,7 @@ impl ManifestFile {
 				},
 				_ => {
 					// if its not a table, it's an error
-					return Err(ManifestFileParseError::ManifestWrongType{ key: key.clone() });
+					return Err(ManifestFileParseError::ManifestWrongType{ key: key.clone(), span: span_of(info, &[key]) });
 				}
 			}
 		}
@@ -350,14 +981,109 @@ impl ManifestFile {
 		Ok(manifest)
 	}
 
-	pub fn dep_names(&self, dep_groups: &Vec<&String>) -> Vec<&str> {
+	// makes the paths of local dependencies and patches relative to the folder the manifest is in
+	pub fn resolve_paths(&mut self, folder: &Path) {
+		for dependency in self.dependencies.values_mut().flatten() {
+			dependency.resolve_paths(folder);
+		}
+	}
+
+	// the dependencies of a group, a manifest without a [dependencies] table just doesn't have any
+	pub fn group(&self, group: &str) -> Result<&[ManifestDependency], crate::SyncError> {
+		match self.dependencies.get(group) {
+			Some(dependencies) => Ok(dependencies),
+			None if group == "dependencies" => Ok(&[]),
+			None => Err(crate::SyncError::ManifestGroup{ group: group.to_string() })
+		}
+	}
+
+	pub fn dep_names(&self, dep_groups: &Vec<&String>) -> Result<Vec<&str>, crate::SyncError> {
 		let mut dep_names: Vec<&str> = vec![];
 		for group in dep_groups {
-			for dep in &self.dependencies[*group] {
+			for dep in self.group(group)? {
 				dep_names.push(&dep.name);
 			}
 		}
 
-		dep_names
+		Ok(dep_names)
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_diagnostics() {
+        let contents = "manifest_version = 1\n\n[dependencies]\nfmt = \"gh:fmtlib/fmt@^x\"\nglm = { type = \"gh\", username = \"g-truc\", repository = \"glm\", commit = \"zz\" }\n";
+        let error = ManifestFile::parse(contents).unwrap_err();
+        assert_eq!(&contents[error.span().unwrap()], "\"gh:fmtlib/fmt@^x\"");
+        let rendered = error.render("wares.toml", contents);
+        assert!(rendered.starts_with("error: Dependency parse error in fmt ([dependencies])"), "{rendered}");
+        assert!(rendered.contains(" --> wares.toml:4:7\n  |\n4 | fmt = \"gh:fmtlib/fmt@^x\"\n  |       ^^^^^^^^^^^^^^^^^^\n  = hint: "), "{rendered}");
+
+        // errors in a table point at the key they're about
+        let contents = contents.replace("@^x", "");
+        let error = ManifestFile::parse(&contents).unwrap_err();
+        assert_eq!(&contents[error.span().unwrap()], "\"zz\"");
+
+        let error = ManifestFile::parse("manifest_version = 1\ndependencies = 3\n").unwrap_err();
+        assert!(error.render("wares.toml", "manifest_version = 1\ndependencies = 3\n").contains(" --> wares.toml:2:16\n"));
+    }
+
+    #[test]
+    fn check_malformed_specifiers() {
+        for spec in ["gh:fmtlib/fmt%1.0", "gh:fmtlib/fmt@", "fmtlib/fmt", "gh:fmtlib/fmt@\u{e9}"] {
+            assert!(ManifestDependency::parse(&Value::String(spec.to_string())).is_err(), "{spec}");
+        }
+
+        let mut table = Table::new();
+        for (key, value) in [("type", "gh"), ("username", "fmtlib"), ("repository", "fmt"), ("tag", "11.0.0")] {
+            table.insert(key.to_string(), Value::String(value.to_string()));
+        }
+        assert!(matches!(ManifestDependency::parse(&Value::Table(table)).unwrap().specifier(), Specifier::Tag(tag) if tag == "11.0.0"));
+    }
+
+    #[test]
+    fn check_strict_keys() {
+        let contents = "manifest_version = 1\n[dependencies]\nfmt = { type = \"gh\", username = \"fmtlib\", repository = \"fmt\", verison = \"^11\" }\n";
+        let error = ManifestFile::parse(contents).unwrap_err();
+        assert!(matches!(&error, ManifestFileParseError::DependencyParse{ source: DependencyParseError::UnknownKey{ key, .. }, .. } if key == "verison"), "{error}");
+        assert_eq!(error.hint().unwrap(), "did you mean version?");
+        assert_eq!(&contents[error.span().unwrap()], "\"^11\"");
+
+        let error = ManifestFile::parse("manifest_version = 1\n[dependencies]\nfmt = { type = \"gh\", username = \"fmtlib\", repository = \"fmt\", tag = \"11.0.0\", branch = \"master\" }\n").unwrap_err();
+        assert!(matches!(&error, ManifestFileParseError::DependencyParse{ source: DependencyParseError::ConflictingSpecifiers{ keys }, .. } if keys == &vec!["branch", "tag"]), "{error}");
+
+        // archives don't have specifiers
+        let error = ManifestFile::parse("manifest_version = 1\n[dependencies]\nzlib = { type = \"archive\", url = \"https://zlib.net/zlib-1.3.1.tar.gz\", version = \"1\" }\n").unwrap_err();
+        assert!(error.hint().unwrap().starts_with("dependencies of type archive take type, url, sha256"), "{error}");
+
+        let error = ManifestFile::parse("manifest_version = 1\n[dev-dependecies]\n").unwrap_err();
+        assert!(matches!(&error, ManifestFileParseError::UnknownGroup{ group, .. } if group == "dev-dependecies"), "{error}");
+        assert_eq!(error.hint().unwrap(), "did you mean dev-dependencies?");
+        assert!(ManifestFile::parse("manifest_version = 1\n[dependencies]\n[desktop-dependencies]\n").is_ok());
+    }
+
+    #[test]
+    fn check_schema() {
+        // the published schema takes the same keys as the parser
+        let schema: serde_json::Value = serde_json::from_str(include_str!("../schema/wares.schema.json")).unwrap();
+        for (definition, dependency_type) in [("git_dependency", "git"), ("forge_dependency", "gh"), ("archive_dependency", "archive"), ("path_dependency", "path")] {
+            let mut keys: Vec<&str> = schema["definitions"][definition]["properties"].as_object().unwrap().keys().map(String::as_str).collect();
+            let mut expected = keys_of(dependency_type);
+            keys.sort();
+            expected.sort();
+            assert_eq!(keys, expected, "{definition}");
+        }
+    }
+
+    #[test]
+    fn check_groups() {
+        let manifest = ManifestFile::parse("manifest_version = 1\n[dev-dependencies]\nfmt = \"gh:fmtlib/fmt@^11\"\n").unwrap();
+        let dev = String::from("dev-dependencies");
+        let test = String::from("test-dependencies");
+        assert_eq!(manifest.dep_names(&vec![&String::from("dependencies"), &dev]).unwrap(), vec!["fmt"]);
+        assert!(matches!(manifest.dep_names(&vec![&test]), Err(crate::SyncError::ManifestGroup{ .. })));
+    }
+}
@@ -0,0 +1,168 @@
+// the versions a project could update to (wares outdated)
+//
+// only dependencies with a version requirement are compared: branches, commits, archives and paths don't have
+// newer versions to move to. the tags are listed the same way they are when locking (see ManifestDependency::versions).
+// a repository that can't be listed gets a row with the error, the rest of the report is still worth having.
+
+// standard libraries
+use std::collections::BTreeMap;
+
+// versioning
+use semver::Version;
+
+// serialization/deserialization
+use serde::Serialize;
+
+// internal dependencies
+use crate::lock::LockFile;
+use crate::manifest::{ManifestFile, ManifestDependency, RemoteRef, Specifier, RefSource, LockingError};
+
+// one row of the report
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Outdated {
+	pub name: String,
+	// the manifest groups that ask for the dependency
+	pub groups: Vec<String>,
+	pub requirement: String,
+	// the version in wares.lock, None if it isn't locked yet
+	pub locked: Option<Version>,
+	// the newest version the requirement allows (what `wares update` would pick)
+	pub compatible: Option<Version>,
+	// the newest version overall, which may need the requirement to be changed
+	pub latest: Option<Version>,
+	// why the versions couldn't be listed, if they couldn't
+	pub error: Option<String>
+}
+
+impl Outdated {
+	// there's something newer than the locked version, compatible or not (or there might be, if it couldn't be checked)
+	pub fn is_outdated(&self) -> bool {
+		if self.error.is_some() {
+			return true;
+		}
+
+		match &self.locked {
+			Some(locked) => [&self.compatible, &self.latest].into_iter().flatten().any(|version| version > locked),
+			None => true
+		}
+	}
+}
+
+// the newest of some versions, pre-releases only count if there's nothing else
+pub(crate) fn newest<'a>(versions: impl DoubleEndedIterator<Item = &'a Version> + Clone) -> Option<&'a Version> {
+	versions.clone().rev().find(|version| version.pre.is_empty()).or(versions.last())
+}
+
+// compares a locked version against the tags of the dependency's repository
+pub fn compare(dependency: &ManifestDependency, groups: Vec<String>, locked: Option<Version>, versions: &BTreeMap<Version, RemoteRef>) -> Outdated {
+	let compatible = dependency.matching_versions(versions).into_iter().next().and_then(|candidate| candidate.version);
+
+	Outdated {
+		name: dependency.name.clone(),
+		groups,
+		requirement: dependency.specifier().to_string(),
+		locked,
+		compatible,
+		latest: newest(versions.keys()).cloned(),
+		error: None
+	}
+}
+
+// a row for a dependency whose versions couldn't be listed
+fn failed(dependency: &ManifestDependency, groups: Vec<String>, locked: Option<Version>, error: LockingError) -> Outdated {
+	Outdated {
+		name: dependency.name.clone(),
+		groups,
+		requirement: dependency.specifier().to_string(),
+		locked,
+		compatible: None,
+		latest: None,
+		error: Some(error.to_string())
+	}
+}
+
+// lists the versions of every dependency in the manifest with a version requirement
+pub fn report(manifest: &ManifestFile, lockfile: &LockFile, groups: &[String], refs: RefSource) -> Vec<Outdated> {
+	// a dependency can be in more than one group, it's only looked up once
+	let mut dependencies: BTreeMap<&str, (&ManifestDependency, Vec<String>)> = BTreeMap::new();
+	for (group, group_dependencies) in &manifest.dependencies {
+		if !groups.is_empty() && !groups.contains(group) {
+			continue;
+		}
+
+		for dependency in group_dependencies {
+			if matches!(dependency.specifier(), Specifier::Version(_)) {
+				dependencies.entry(&dependency.name).or_insert((dependency, Vec::new())).1.push(group.clone());
+			}
+		}
+	}
+
+	let mut rows = Vec::new();
+	for (name, (dependency, groups)) in dependencies {
+		let locked = lockfile.dependencies.get(name).and_then(|locked| locked.version.clone());
+		rows.push(match dependency.versions(refs) {
+			Ok(versions) => compare(dependency, groups, locked, &versions),
+			Err(error) => failed(dependency, groups, locked, error)
+		});
+	}
+
+	rows
+}
+
+// the report as a table with a column for each version
+pub fn render_table(rows: &[Outdated]) -> String {
+	let version = |version: &Option<Version>| version.as_ref().map_or(String::from("-"), |version| version.to_string());
+
+	let mut lines = vec![[String::from("Name"), String::from("Group"), String::from("Requirement"), String::from("Locked"), String::from("Compatible"), String::from("Latest")]];
+	for row in rows {
+		lines.push([row.name.clone(), row.groups.join(", "), row.requirement.clone(), version(&row.locked), version(&row.compatible), version(&row.latest)]);
+	}
+
+	let mut widths = [0; 6];
+	for line in &lines {
+		for (width, cell) in widths.iter_mut().zip(line) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+
+	let mut output = String::new();
+	for line in lines {
+		let cells: Vec<String> = line.iter().zip(widths).map(|(cell, width)| format!("{cell:<width$}")).collect();
+		output += cells.join("  ").trim_end();
+		output += "\n";
+	}
+	output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml::Value;
+
+    #[test]
+    fn check_compare() {
+        let mut versions = BTreeMap::new();
+        for version in ["1.2.0", "1.4.1", "2.0.0", "3.0.0-rc.1"] {
+            versions.insert(Version::parse(version).unwrap(), RemoteRef { name: format!("refs/tags/v{version}"), oid: git2::Oid::zero() });
+        }
+
+        let mut dependency = ManifestDependency::parse(&Value::String(String::from("gh:fmtlib/fmt@^1.2"))).unwrap();
+        dependency.name = String::from("fmt");
+
+        let row = compare(&dependency, vec![String::from("dependencies")], Some(Version::new(1, 2, 0)), &versions);
+        assert_eq!(row.compatible, Some(Version::new(1, 4, 1)));
+        assert_eq!(row.latest, Some(Version::new(2, 0, 0)));
+        assert!(row.is_outdated());
+
+        let current = compare(&dependency, vec![String::from("dependencies")], Some(Version::new(2, 0, 0)), &versions);
+        assert!(!current.is_outdated());
+
+        let unreachable = failed(&dependency, vec![String::from("dependencies")], Some(Version::new(1, 2, 0)), LockingError::NoMatch { requirement: String::from("^1.2") });
+        assert!(unreachable.is_outdated());
+        assert_eq!(unreachable.latest, None);
+        assert!(unreachable.error.is_some());
+
+        assert_eq!(render_table(&[row]), "Name  Group         Requirement  Locked  Compatible  Latest\n\
+            fmt   dependencies  ^1.2         1.2.0   1.4.1       2.0.0\n");
+    }
+}
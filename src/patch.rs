@@ -0,0 +1,186 @@
+// patches applied to dependencies after they're checked out
+//
+// patches are unified diffs (i.e. from `git diff` or `diff -u`), applied in order with the same rules as
+// `git apply`: every hunk has to match exactly or the install fails. the patch set is identified by the hash
+// of the patch files, which is part of the cache folder's name so patched and unpatched copies never mix.
+
+// standard libraries
+use std::fs;
+use std::path::Path;
+use std::cell::RefCell;
+
+// git2
+use git2::{ApplyLocation, ApplyOptions, Diff, Repository};
+
+// hashing
+use sha2::{Sha256, Digest};
+
+// serialization/deserialization
+use serde::{Serialize, Deserialize};
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::utils;
+use crate::{SyncError, IoSnafu, GitSnafu, PatchSnafu};
+
+// the patches of a locked dependency
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PatchSet {
+	// absolute paths while wares is running, relative to the lock file inside of it
+	pub files: Vec<String>,
+	// the sha256 of the patch files (see hash_files)
+	pub sha256: String
+}
+
+impl PatchSet {
+	// reads the patch files to find their hash
+	pub fn new(files: Vec<String>) -> Result<PatchSet, SyncError> {
+		let sha256 = hash_files(&files)?;
+		Ok(PatchSet { files, sha256 })
+	}
+}
+
+// the sha256 of every patch file, in order
+pub fn hash_files(files: &[String]) -> Result<String, SyncError> {
+	let mut hasher = Sha256::new();
+	for file in files {
+		let contents = fs::read(file).context(IoSnafu{ context: format!("reading the patch {file}") })?;
+		hasher.update((contents.len() as u64).to_le_bytes());
+		hasher.update(&contents);
+	}
+
+	let digest: [u8; 32] = hasher.finalize().into();
+	Ok(utils::format_hex(&digest))
+}
+
+// libgit2 only reads git style patches, so plain unified diffs (from `diff -u`) get the headers git would write
+// the first folder of every path is dropped (like `patch -p1`)
+fn git_style(patch: &str) -> String {
+	if patch.starts_with("diff --git ") || patch.contains("\ndiff --git ") {
+		return patch.to_string();
+	}
+
+	fn strip(path: &str) -> Option<String> {
+		// diff -u puts the modification time after a tab
+		let path = path.split('\t').next().unwrap_or(path).trim_end();
+		if path == "/dev/null" {
+			return None;
+		}
+		Some(path.split_once('/').map_or(path, |(_, rest)| rest).to_string())
+	}
+
+	let lines: Vec<&str> = patch.split_inclusive('\n').collect();
+	let mut result = String::with_capacity(patch.len());
+	let mut i = 0;
+	while i < lines.len() {
+		let (Some(old), Some(new)) = (lines[i].strip_prefix("--- "), lines.get(i + 1).and_then(|line| line.strip_prefix("+++ "))) else {
+			result += lines[i];
+			i += 1;
+			continue;
+		};
+
+		let (old, new) = (strip(old), strip(new));
+		let path = new.clone().or(old.clone()).unwrap_or_default();
+		result += &format!("diff --git a/{path} b/{path}\n");
+		match &old {
+			Some(old) => result += &format!("--- a/{old}\n"),
+			None => result += "new file mode 100644\n--- /dev/null\n"
+		}
+		match &new {
+			Some(new) => result += &format!("+++ b/{new}\n"),
+			None => result += "+++ /dev/null\n"
+		}
+		i += 2;
+	}
+
+	result
+}
+
+// applies a single patch to the files in folder
+fn apply_file(repository: &Repository, file: &str) -> Result<(), SyncError> {
+	let failed = |message: String| PatchSnafu{ patch: file.to_string(), message }.fail();
+
+	let contents = fs::read_to_string(file).context(IoSnafu{ context: format!("reading the patch {file}") })?;
+	let diff = match Diff::from_buffer(git_style(&contents).as_bytes()) {
+		Ok(diff) => diff,
+		Err(error) => return failed(format!("it isn't a unified diff ({})", error.message()))
+	};
+
+	// libgit2 only says that the patch didn't apply, so remember the last file and hunk it looked at
+	let last_file: RefCell<Option<String>> = RefCell::new(None);
+	let last_hunk: RefCell<Option<String>> = RefCell::new(None);
+
+	let mut options = ApplyOptions::new();
+	options.delta_callback(|delta| {
+		*last_file.borrow_mut() = delta
+			.and_then(|delta| delta.new_file().path().or(delta.old_file().path()))
+			.map(|path| path.display().to_string());
+		*last_hunk.borrow_mut() = None;
+		true
+	});
+	options.hunk_callback(|hunk| {
+		*last_hunk.borrow_mut() = hunk.map(|hunk| String::from_utf8_lossy(hunk.header()).trim().to_string());
+		true
+	});
+
+	let result = repository.apply(&diff, ApplyLocation::WorkDir, Some(&mut options));
+	drop(options);
+
+	if let Err(error) = result {
+		let message = match (last_file.into_inner(), last_hunk.into_inner()) {
+			(Some(file), Some(hunk)) => format!("the hunk {hunk} of {file} doesn't apply ({})", error.message()),
+			(Some(file), None) => format!("{file} doesn't apply ({})", error.message()),
+			_ => error.message().to_string()
+		};
+		return failed(message);
+	}
+
+	Ok(())
+}
+
+// applies the patches in order to a checkout (or extracted archive) at folder
+pub fn apply(folder: &Path, files: &[String]) -> Result<(), SyncError> {
+	// extracted archives aren't repositories, so they get a throwaway one to apply the patches with
+	let (repository, temporary) = match Repository::open(folder) {
+		Ok(repository) => (repository, false),
+		Err(_) => (Repository::init(folder).context(GitSnafu)?, true)
+	};
+
+	let result = files.iter().try_for_each(|file| apply_file(&repository, file));
+
+	if temporary {
+		drop(repository);
+		utils::remove_dir_all(&folder.join(".git")).context(IoSnafu{ context: format!("removing the temporary repository in {:?}", folder) })?;
+	}
+
+	result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_apply() {
+        let folder = std::env::temp_dir().join(format!("wares-patch-test-{}", std::process::id()));
+        let _ = utils::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("glfw.h"), "#pragma once\nint glfwInit(void);\n").unwrap();
+
+        let patch = folder.join("fix.patch");
+        fs::write(&patch, "--- a/glfw.h\n+++ b/glfw.h\n@@ -1,2 +1,3 @@\n #pragma once\n+#include <stddef.h>\n int glfwInit(void);\n").unwrap();
+        let patch = patch.to_string_lossy().to_string();
+
+        apply(&folder, std::slice::from_ref(&patch)).unwrap();
+        assert_eq!(fs::read_to_string(folder.join("glfw.h")).unwrap(), "#pragma once\n#include <stddef.h>\nint glfwInit(void);\n");
+        assert!(!folder.join(".git").exists());
+
+        // the same hunk can't be applied twice
+        let error = apply(&folder, &[patch]).unwrap_err().to_string();
+        assert!(error.contains("@@ -1,2 +1,3 @@") && error.contains("glfw.h"), "{error}");
+
+        utils::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+// terminal progress display
+//
+// every package gets one line showing what's currently happening to it (resolving, fetching, installed, ...).
+// on a terminal the lines are redrawn in place as packages change state, otherwise (i.e. in a CI log or when
+// cmake captures the output) each change is printed as a new line.
+
+// standard libraries
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+
+// terminal ui
+use colored::Colorize;
+
+#[derive(Clone, Debug)]
+pub enum Status {
+	Working(String),
+	Done(String),
+	Failed(String)
+}
+
+impl Status {
+	fn render(&self, name: &str, width: usize) -> String {
+		match self {
+			Status::Working(message) => format!("{} {:width$} {}", "~".blue(), name, message),
+			Status::Done(message) => format!("{} {:width$} {}", "✓".green(), name, message),
+			Status::Failed(message) => format!("{} {:width$} {}", "✗".red(), name, message.red())
+		}
+	}
+}
+
+struct Lines {
+	lines: Vec<(String, Status)>,
+	// how many lines are currently on the screen
+	drawn: usize
+}
+
+pub struct Progress {
+	lines: Mutex<Lines>,
+	interactive: bool
+}
+
+impl Progress {
+	pub fn new() -> Progress {
+		Progress { lines: Mutex::new(Lines { lines: Vec::new(), drawn: 0 }), interactive: io::stderr().is_terminal() }
+	}
+
+	// the line of a package, created the first time the package is seen
+	pub fn line(&self, name: &str) -> Line<'_> {
+		let mut lines = self.lines.lock().unwrap();
+		let index = match lines.lines.iter().position(|(line_name, _)| line_name == name) {
+			Some(index) => index,
+			None => {
+				lines.lines.push((name.to_string(), Status::Working(String::from("waiting"))));
+				lines.lines.len() - 1
+			}
+		};
+
+		Line { progress: self, index }
+	}
+
+	// leaves the lines that are on the screen alone, so other output can be printed below them
+	// the next phase starts with no lines, the lines (and their handles) of this one are done with
+	pub fn finish(&self) {
+		let mut lines = self.lines.lock().unwrap();
+		lines.lines.clear();
+		lines.drawn = 0;
+	}
+
+	fn set(&self, index: usize, status: Status) {
+		let mut lines = self.lines.lock().unwrap();
+		lines.lines[index].1 = status;
+
+		let width = lines.lines.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+		let mut stderr = io::stderr().lock();
+
+		if self.interactive {
+			// move back up to the first line and draw everything again
+			if lines.drawn > 0 {
+				let _ = write!(stderr, "\x1b[{}A", lines.drawn);
+			}
+			for (name, status) in &lines.lines {
+				let _ = writeln!(stderr, "\x1b[2K{}", status.render(name, width));
+			}
+			lines.drawn = lines.lines.len();
+		} else {
+			let (name, status) = &lines.lines[index];
+			let _ = writeln!(stderr, "{}", status.render(name, width));
+		}
+	}
+}
+
+impl Default for Progress {
+	fn default() -> Self {
+		Progress::new()
+	}
+}
+
+// a handle to one package's line
+#[derive(Clone, Copy)]
+pub struct Line<'a> {
+	progress: &'a Progress,
+	index: usize
+}
+
+impl Line<'_> {
+	pub fn working<S: Into<String>>(&self, message: S) {
+		self.progress.set(self.index, Status::Working(message.into()));
+	}
+
+	pub fn done<S: Into<String>>(&self, message: S) {
+		self.progress.set(self.index, Status::Done(message.into()));
+	}
+
+	pub fn failed<S: Into<String>>(&self, message: S) {
+		self.progress.set(self.index, Status::Failed(message.into()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_finish() {
+        let progress = Progress::new();
+        progress.line("fmt").done("installed");
+        progress.line("glfw").done("installed");
+        progress.finish();
+
+        // the next phase doesn't draw the packages of the last one again
+        assert_eq!(progress.line("glfw").index, 0);
+        assert_eq!(progress.lines.lock().unwrap().lines.len(), 1);
+    }
+}
@@ -0,0 +1,546 @@
+// dependency resolution
+//
+// wares uses a small backtracking solver: packages are decided one at a time, newest candidate first,
+// and every candidate has to satisfy all of the requirements placed on its package so far. when a choice
+// leads to a conflict, the solver backs up to the most recent package involved in the conflict and
+// tries its next candidate. if no combination works, the last conflict is reported along with the
+// chain of dependencies that produced each requirement. when locking again, the previous lock of each
+// package is tried before its newest candidate, so only the packages that have to change are changed.
+
+// standard libraries
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::fmt;
+
+// versioning
+use semver::Version;
+
+// error handling
+use snafu::ResultExt;
+
+// internal imports
+use crate::manifest::{ManifestDependency, RefSource};
+use crate::lock::LockedDependency;
+use crate::progress::Progress;
+use crate::{utils, SyncError, IoSnafu, LockSnafu};
+
+// answers the questions the solver needs to ask about packages
+pub trait PackageSource {
+	// every locked dependency that satisfies the manifest dependency, in order of preference
+	fn candidates(&mut self, dependency: &ManifestDependency) -> Result<Vec<LockedDependency>, SyncError>;
+
+	// the dependencies declared by a candidate's own wares.toml
+	fn dependencies(&mut self, name: &str, candidate: &LockedDependency) -> Result<Vec<ManifestDependency>, SyncError>;
+
+	// asks for the candidates of several dependencies at once, sources that can ask in parallel should override this
+	fn candidates_of(&mut self, dependencies: &[ManifestDependency]) -> Vec<Result<Vec<LockedDependency>, SyncError>> {
+		dependencies.iter().map(|dependency| self.candidates(dependency)).collect()
+	}
+
+	// asks for the dependencies of several candidates at once, sources that can ask in parallel should override this
+	fn dependencies_of(&mut self, candidates: &[(String, LockedDependency)]) -> Vec<Result<Vec<ManifestDependency>, SyncError>> {
+		candidates.iter().map(|(name, candidate)| self.dependencies(name, candidate)).collect()
+	}
+}
+
+// everything about a dependency except its name decides its candidates
+fn candidate_key(dependency: &ManifestDependency) -> String {
+	let mut key = dependency.clone();
+	key.name.clear();
+	format!("{:?}", key)
+}
+
+fn dependency_key(name: &str, candidate: &LockedDependency) -> String {
+	format!("{} {:?}", name, candidate)
+}
+
+// one link in the chain that led to a requirement
+#[derive(Clone, Debug)]
+pub struct Step {
+	pub package: Option<String>, // None for a manifest group
+	pub description: String
+}
+
+// a requirement placed on a package and the chain of dependencies that placed it
+#[derive(Clone, Debug)]
+pub struct Requirement {
+	pub path: Vec<Step>,
+	pub dependency: ManifestDependency
+}
+
+impl Requirement {
+	// a requirement that comes straight from a group in the manifest
+	pub fn root(group: &str, dependency: ManifestDependency) -> Requirement {
+		Requirement { path: vec![Step{ package: None, description: format!("wares.toml [{group}]") }], dependency }
+	}
+
+	fn involves(&self, package: &str) -> bool {
+		self.dependency.name == package || self.path.iter().any(|step| step.package.as_deref() == Some(package))
+	}
+}
+
+impl fmt::Display for Requirement {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for step in &self.path {
+			write!(f, "{} -> ", step.description)?;
+		}
+		write!(f, "{} {}", self.dependency.name, self.dependency.specifier())
+	}
+}
+
+// explains why a package couldn't be resolved
+#[derive(Debug)]
+pub struct Conflict {
+	pub package: String,
+	pub requirements: Vec<Requirement>,
+	// what the package was already locked to by another project sharing the lock file
+	pub locked: Option<LockedDependency>,
+	// packages whose every candidate ran into this conflict
+	pub exhausted: Vec<String>
+}
+
+impl Conflict {
+	fn involves(&self, package: &str) -> bool {
+		self.package == package || self.requirements.iter().any(|requirement| requirement.involves(package))
+	}
+}
+
+impl fmt::Display for Conflict {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "no version of {} satisfies every requirement:", self.package)?;
+		for requirement in &self.requirements {
+			write!(f, "\n    {requirement}")?;
+		}
+		if let Some(locked) = &self.locked {
+			write!(f, "\n    wares.lock already locks {} to {locked}", self.package)?;
+		}
+		for package in &self.exhausted {
+			write!(f, "\n  and no other candidate of {package} avoids this conflict")?;
+		}
+		Ok(())
+	}
+}
+
+pub struct Resolver<'a, S: PackageSource> {
+	source: &'a mut S,
+	// packages that were already decided and can't be changed
+	pinned: BTreeMap<String, LockedDependency>,
+	// packages decided by the last lock, which are kept as long as they still satisfy every requirement
+	preferred: BTreeMap<String, LockedDependency>,
+	// the exact versions some packages have to be locked to (wares update --precise)
+	precise: BTreeMap<String, Version>,
+	// answers from the source, so every question is only asked once
+	candidate_cache: BTreeMap<String, Vec<LockedDependency>>,
+	dependency_cache: BTreeMap<String, Vec<ManifestDependency>>
+}
+
+impl<'a, S: PackageSource> Resolver<'a, S> {
+	pub fn new(source: &'a mut S, pinned: BTreeMap<String, LockedDependency>) -> Resolver<'a, S> {
+		Resolver { source, pinned, preferred: BTreeMap::new(), precise: BTreeMap::new(), candidate_cache: BTreeMap::new(), dependency_cache: BTreeMap::new() }
+	}
+
+	pub fn set_preferred(&mut self, preferred: BTreeMap<String, LockedDependency>) {
+		self.preferred = preferred;
+	}
+
+	pub fn set_precise(&mut self, name: &str, version: Version) {
+		self.precise.insert(name.to_string(), version);
+	}
+
+	// finds one locked dependency for every package reachable from the root requirements
+	pub fn resolve(&mut self, roots: Vec<Requirement>) -> Result<BTreeMap<String, LockedDependency>, SyncError> {
+		let mut requirements = roots;
+		let mut chosen: BTreeMap<String, LockedDependency> = BTreeMap::new();
+
+		match self.solve(&mut requirements, &mut chosen)? {
+			Ok(()) => Ok(chosen),
+			Err(conflict) => Err(SyncError::Resolution{ conflict: Box::new(conflict) })
+		}
+	}
+
+	fn candidates(&mut self, dependency: &ManifestDependency) -> Result<Vec<LockedDependency>, SyncError> {
+		let key = candidate_key(dependency);
+		if !self.candidate_cache.contains_key(&key) {
+			let candidates = self.source.candidates(dependency)?;
+			self.candidate_cache.insert(key.clone(), candidates);
+		}
+
+		Ok(self.candidate_cache[&key].clone())
+	}
+
+	fn dependencies(&mut self, name: &str, candidate: &LockedDependency) -> Result<Vec<ManifestDependency>, SyncError> {
+		let key = dependency_key(name, candidate);
+		if !self.dependency_cache.contains_key(&key) {
+			let dependencies = self.source.dependencies(name, candidate)?;
+			self.dependency_cache.insert(key.clone(), dependencies);
+		}
+
+		Ok(self.dependency_cache[&key].clone())
+	}
+
+	// asks the source about every undecided package at once, so the slow parts (ls-remote, cloning) can run in parallel
+	// failures are left for the solver to run into again, so they're reported with the right context
+	fn prefetch(&mut self, requirements: &[Requirement], chosen: &BTreeMap<String, LockedDependency>) {
+		let undecided: Vec<&Requirement> = requirements.iter().filter(|requirement| !chosen.contains_key(&requirement.dependency.name)).collect();
+
+		let mut dependencies: Vec<ManifestDependency> = Vec::new();
+		for requirement in &undecided {
+			let key = candidate_key(&requirement.dependency);
+			if !self.candidate_cache.contains_key(&key) && !dependencies.iter().any(|dependency| candidate_key(dependency) == key) {
+				dependencies.push(requirement.dependency.clone());
+			}
+		}
+		for (dependency, candidates) in dependencies.iter().zip(self.source.candidates_of(&dependencies)) {
+			if let Ok(candidates) = candidates {
+				self.candidate_cache.insert(candidate_key(dependency), candidates);
+			}
+		}
+
+		// the candidate a package is most likely decided to is the newest one every requirement on it agrees on
+		let mut likely: Vec<(String, LockedDependency)> = Vec::new();
+		for requirement in &undecided {
+			let name = &requirement.dependency.name;
+			if likely.iter().any(|(likely_name, _)| likely_name == name) {
+				continue;
+			}
+
+			let candidate = match self.pinned.get(name).or(self.preferred.get(name)) {
+				Some(pinned) => Some(pinned.clone()),
+				None => self.candidate_cache.get(&candidate_key(&requirement.dependency)).and_then(|candidates| {
+					candidates.iter().find(|candidate| {
+						undecided.iter().filter(|other| &other.dependency.name == name).all(|other| {
+							self.candidate_cache.get(&candidate_key(&other.dependency)).is_some_and(|others| others.iter().any(|other| other.same_source(candidate)))
+						})
+					}).cloned()
+				})
+			};
+
+			if let Some(candidate) = candidate {
+				if !self.dependency_cache.contains_key(&dependency_key(name, &candidate)) {
+					likely.push((name.clone(), candidate));
+				}
+			}
+		}
+		for ((name, candidate), dependencies) in likely.iter().zip(self.source.dependencies_of(&likely)) {
+			if let Ok(dependencies) = dependencies {
+				self.dependency_cache.insert(dependency_key(name, candidate), dependencies);
+			}
+		}
+	}
+
+	// true if the locked dependency is one of the candidates of the requirement
+	fn satisfies(&mut self, locked: &LockedDependency, requirement: &Requirement) -> Result<bool, SyncError> {
+		Ok(self.candidates(&requirement.dependency)?.iter().any(|candidate| candidate.same_source(locked)))
+	}
+
+	fn conflict(&self, package: &str, requirements: &[Requirement]) -> Conflict {
+		Conflict {
+			package: package.to_string(),
+			requirements: requirements.iter().filter(|requirement| requirement.dependency.name == package).cloned().collect(),
+			locked: self.pinned.get(package).cloned(),
+			exhausted: Vec::new()
+		}
+	}
+
+	// decides the next undecided package, recursing until everything is decided or a conflict can't be avoided
+	fn solve(&mut self, requirements: &mut Vec<Requirement>, chosen: &mut BTreeMap<String, LockedDependency>) -> Result<Result<(), Conflict>, SyncError> {
+		let next = requirements.iter().find(|requirement| !chosen.contains_key(&requirement.dependency.name)).cloned();
+		let Some(first_requirement) = next else {
+			return Ok(Ok(()));
+		};
+		let name = first_requirement.dependency.name.clone();
+
+		self.prefetch(requirements, chosen);
+
+		// only keep the candidates that every requirement on this package agrees on
+		let mut candidates = match self.pinned.get(&name) {
+			Some(pinned) => vec![pinned.clone()],
+			None => self.candidates(&first_requirement.dependency)?
+		};
+		for requirement in requirements.iter().filter(|requirement| requirement.dependency.name == name).cloned().collect::<Vec<Requirement>>() {
+			let mut agreed = Vec::new();
+			for candidate in candidates {
+				if self.satisfies(&candidate, &requirement)? {
+					agreed.push(candidate);
+				}
+			}
+			candidates = agreed;
+		}
+
+		if let Some(version) = self.precise.get(&name) {
+			candidates.retain(|candidate| candidate.version.as_ref() == Some(version));
+		}
+
+		// the last lock goes first, so locking again doesn't bump packages nobody asked to update
+		if let Some(preferred) = self.preferred.get(&name) {
+			if let Some(position) = candidates.iter().position(|candidate| candidate.same_source(preferred)) {
+				let candidate = candidates.remove(position);
+				candidates.insert(0, candidate);
+			}
+		}
+
+		let mut last_conflict = self.conflict(&name, requirements);
+
+		for candidate in candidates {
+			let dependencies = self.dependencies(&name, &candidate)?;
+
+			let mut path = first_requirement.path.clone();
+			path.push(Step{ package: Some(name.clone()), description: format!("{name} {candidate}") });
+			let new_requirements: Vec<Requirement> = dependencies.iter()
+				.map(|dependency| Requirement{ path: path.clone(), dependency: dependency.clone() })
+				.collect();
+
+			// new requirements on packages that are already decided have to agree with those decisions
+			let mut disagreement: Option<Conflict> = None;
+			for requirement in &new_requirements {
+				if let Some(existing) = chosen.get(&requirement.dependency.name).cloned() {
+					if !self.satisfies(&existing, requirement)? {
+						let mut all_requirements = requirements.clone();
+						all_requirements.push(requirement.clone());
+						disagreement = Some(self.conflict(&requirement.dependency.name, &all_requirements));
+						break;
+					}
+				}
+			}
+
+			if let Some(conflict) = disagreement {
+				last_conflict = conflict;
+				continue;
+			}
+
+			let mut locked = candidate.clone();
+			locked.dependencies = dependencies.iter().map(|dependency| dependency.name.clone()).collect();
+
+			let previous_len = requirements.len();
+			requirements.extend(new_requirements);
+			chosen.insert(name.clone(), locked);
+
+			match self.solve(requirements, chosen)? {
+				Ok(()) => return Ok(Ok(())),
+				Err(conflict) => {
+					requirements.truncate(previous_len);
+					chosen.remove(&name);
+
+					// picking another candidate for this package can't fix a conflict it isn't part of
+					if !conflict.involves(&name) {
+						return Ok(Err(conflict));
+					}
+					last_conflict = conflict;
+				}
+			}
+		}
+
+		if last_conflict.package != name && !last_conflict.exhausted.contains(&name) {
+			last_conflict.exhausted.push(name);
+		}
+
+		Ok(Err(last_conflict))
+	}
+}
+
+// the package source used by wares: versions come from the remote repositories, while dependencies
+// come from the wares.toml of each candidate once it's installed in the cache
+pub struct CacheSource<'a> {
+	cache_folder: &'a Path,
+	overrides: &'a BTreeMap<String, String>,
+	offline: bool,
+	// how many packages are locked/installed at the same time
+	jobs: usize,
+	progress: &'a Progress
+}
+
+impl<'a> CacheSource<'a> {
+	pub fn new(cache_folder: &'a Path, overrides: &'a BTreeMap<String, String>, offline: bool, jobs: usize, progress: &'a Progress) -> CacheSource<'a> {
+		CacheSource { cache_folder, overrides, offline, jobs, progress }
+	}
+
+	fn refs(&self) -> RefSource<'a> {
+		if self.offline {
+			RefSource::Cache(self.cache_folder)
+		} else {
+			RefSource::Remote
+		}
+	}
+
+	fn lock(&self, dependency: &ManifestDependency) -> Result<Vec<LockedDependency>, SyncError> {
+		let line = self.progress.line(&dependency.name);
+		line.working(format!("resolving {}", dependency.specifier()));
+
+		match dependency.candidates(self.refs()).context(LockSnafu) {
+			Ok(candidates) => {
+				line.working(format!("found {} candidate(s) for {}", candidates.len(), dependency.specifier()));
+				Ok(candidates)
+			},
+			Err(error) => {
+				line.failed(error.to_string());
+				Err(error)
+			}
+		}
+	}
+
+	fn read_dependencies(&self, name: &str, candidate: &LockedDependency) -> Result<Vec<ManifestDependency>, SyncError> {
+		// overridden dependencies are read from their override folder instead of the cache
+		let folder: PathBuf = match self.overrides.get(name) {
+			Some(folder) => utils::get_full_path(folder).context(IoSnafu{ context: format!("grabbing full path of {folder}") })?,
+			None => PathBuf::from(candidate.install(self.cache_folder, self.offline, self.progress.line(name))?)
+		};
+
+		// only the default group is transitive, the other groups are for developing the dependency itself
+		Ok(match crate::read_nested_manifest(&folder)? {
+			Some(mut manifest) => manifest.dependencies.remove("dependencies").unwrap_or_default(),
+			None => Vec::new()
+		})
+	}
+}
+
+impl PackageSource for CacheSource<'_> {
+	fn candidates(&mut self, dependency: &ManifestDependency) -> Result<Vec<LockedDependency>, SyncError> {
+		self.lock(dependency)
+	}
+
+	fn dependencies(&mut self, name: &str, candidate: &LockedDependency) -> Result<Vec<ManifestDependency>, SyncError> {
+		self.read_dependencies(name, candidate)
+	}
+
+	fn candidates_of(&mut self, dependencies: &[ManifestDependency]) -> Vec<Result<Vec<LockedDependency>, SyncError>> {
+		utils::parallel_map(dependencies, self.jobs, |dependency| self.lock(dependency))
+	}
+
+	fn dependencies_of(&mut self, candidates: &[(String, LockedDependency)]) -> Vec<Result<Vec<ManifestDependency>, SyncError>> {
+		utils::parallel_map(candidates, self.jobs, |(name, candidate)| self.read_dependencies(name, candidate))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::{Specifier, RemoteRef};
+    use crate::lock::LockedDependencyId;
+    use semver::VersionReq;
+
+    // an in-memory package source: package name -> (version, dependencies of that version)
+    struct TestSource {
+        packages: BTreeMap<String, Vec<(Version, Vec<ManifestDependency>)>>
+    }
+
+    impl TestSource {
+        fn new() -> TestSource {
+            TestSource { packages: BTreeMap::new() }
+        }
+
+        fn add(&mut self, name: &str, version: &str, dependencies: Vec<ManifestDependency>) {
+            self.packages.entry(name.to_string()).or_default().push((Version::parse(version).unwrap(), dependencies));
+        }
+
+        fn oid(name: &str, version: &Version) -> git2::Oid {
+            let id = format!("{name}{version}").bytes().fold(0u64, |hash, byte| hash.wrapping_mul(31).wrapping_add(byte as u64));
+            git2::Oid::from_str(&format!("{:040x}", id)).unwrap()
+        }
+    }
+
+    impl PackageSource for TestSource {
+        fn candidates(&mut self, dependency: &ManifestDependency) -> Result<Vec<LockedDependency>, SyncError> {
+            let versions = self.packages.get(&dependency.name).map(|versions| {
+                versions.iter().map(|(version, _)| {
+                    (version.clone(), RemoteRef{ name: format!("refs/tags/v{version}"), oid: TestSource::oid(&dependency.name, version) })
+                }).collect()
+            }).unwrap_or_default();
+            Ok(dependency.matching_versions(&versions))
+        }
+
+        fn dependencies(&mut self, name: &str, candidate: &LockedDependency) -> Result<Vec<ManifestDependency>, SyncError> {
+            Ok(self.packages[name].iter()
+                .find(|(version, _)| Some(version) == candidate.version.as_ref())
+                .map(|(_, dependencies)| dependencies.clone())
+                .unwrap_or_default())
+        }
+    }
+
+    fn dep(name: &str, requirement: &str) -> ManifestDependency {
+        ManifestDependency::new(name.to_string(), format!("https://github.com/test/{name}.git"), Specifier::Version(VersionReq::parse(requirement).unwrap()))
+    }
+
+    fn version_of(solution: &BTreeMap<String, LockedDependency>, name: &str) -> String {
+        solution[name].version.as_ref().unwrap().to_string()
+    }
+
+    #[test]
+    fn check_backtracking() {
+        let mut source = TestSource::new();
+        source.add("engine", "1.0.0", vec![dep("fmt", "^9")]);
+        source.add("engine", "1.1.0", vec![dep("fmt", "^10")]);
+        source.add("fmt", "9.1.0", vec![]);
+        source.add("fmt", "10.2.1", vec![]);
+
+        let mut resolver = Resolver::new(&mut source, BTreeMap::new());
+        let solution = resolver.resolve(vec![Requirement::root("dependencies", dep("engine", "^1")), Requirement::root("dependencies", dep("fmt", "^9"))]).unwrap();
+
+        assert_eq!(version_of(&solution, "engine"), "1.0.0");
+        assert_eq!(version_of(&solution, "fmt"), "9.1.0");
+        assert_eq!(solution["engine"].dependencies, vec!["fmt".to_string()]);
+    }
+
+    #[test]
+    fn check_conflict_explanation() {
+        let mut source = TestSource::new();
+        source.add("spdlog", "1.14.1", vec![dep("fmt", "^9")]);
+        source.add("fmt", "9.1.0", vec![]);
+        source.add("fmt", "10.2.1", vec![]);
+
+        let mut resolver = Resolver::new(&mut source, BTreeMap::new());
+        let error = resolver.resolve(vec![Requirement::root("dependencies", dep("fmt", "^10")), Requirement::root("dependencies", dep("spdlog", "^1"))]).unwrap_err();
+
+        let SyncError::Resolution{ conflict } = error else {
+            panic!("expected a resolution error, got: {error}");
+        };
+        assert_eq!(conflict.package, "fmt");
+        assert_eq!(conflict.to_string(), "no version of fmt satisfies every requirement:\n    \
+            wares.toml [dependencies] -> fmt ^10\n    \
+            wares.toml [dependencies] -> spdlog 1.14.1 -> fmt ^9\n  \
+            and no other candidate of spdlog avoids this conflict");
+    }
+
+    #[test]
+    fn check_pinned() {
+        let mut source = TestSource::new();
+        source.add("fmt", "9.1.0", vec![]);
+        source.add("fmt", "10.2.1", vec![]);
+
+        let mut pinned = LockedDependency::new("https://github.com/test/fmt.git".to_string(), LockedDependencyId::Oid(TestSource::oid("fmt", &Version::new(9, 1, 0))));
+        pinned.version = Some(Version::new(9, 1, 0));
+
+        let mut resolver = Resolver::new(&mut source, BTreeMap::from([("fmt".to_string(), pinned)]));
+        let solution = resolver.resolve(vec![Requirement::root("dependencies", dep("fmt", "*"))]).unwrap();
+        assert_eq!(version_of(&solution, "fmt"), "9.1.0");
+    }
+
+    #[test]
+    fn check_preferred() {
+        let mut source = TestSource::new();
+        source.add("fmt", "9.1.0", vec![]);
+        source.add("fmt", "10.2.1", vec![]);
+        source.add("glm", "0.9.9", vec![]);
+        source.add("glm", "1.0.1", vec![]);
+
+        let mut previous = LockedDependency::new("https://github.com/test/fmt.git".to_string(), LockedDependencyId::Oid(TestSource::oid("fmt", &Version::new(9, 1, 0))));
+        previous.version = Some(Version::new(9, 1, 0));
+
+        // the previous lock is kept while it still matches
+        let mut resolver = Resolver::new(&mut source, BTreeMap::new());
+        resolver.set_preferred(BTreeMap::from([("fmt".to_string(), previous.clone())]));
+        let solution = resolver.resolve(vec![Requirement::root("dependencies", dep("fmt", "*")), Requirement::root("dependencies", dep("glm", "*"))]).unwrap();
+        assert_eq!(version_of(&solution, "fmt"), "9.1.0");
+        assert_eq!(version_of(&solution, "glm"), "1.0.1");
+
+        // and replaced once it doesn't
+        let mut resolver = Resolver::new(&mut source, BTreeMap::new());
+        resolver.set_preferred(BTreeMap::from([("fmt".to_string(), previous)]));
+        let solution = resolver.resolve(vec![Requirement::root("dependencies", dep("fmt", "^10"))]).unwrap();
+        assert_eq!(version_of(&solution, "fmt"), "10.2.1");
+
+        let mut resolver = Resolver::new(&mut source, BTreeMap::new());
+        resolver.set_precise("glm", Version::new(0, 9, 9));
+        let solution = resolver.resolve(vec![Requirement::root("dependencies", dep("glm", "*"))]).unwrap();
+        assert_eq!(version_of(&solution, "glm"), "0.9.9");
+    }
+}
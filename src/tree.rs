@@ -0,0 +1,264 @@
+// the dependency graph of a project (wares tree)
+//
+// the graph comes from wares.toml (the groups and their direct dependencies) and wares.lock (what every
+// package is locked to and what it depends on in turn), nothing is fetched or installed to print it.
+
+// standard libraries
+use std::collections::BTreeSet;
+use std::path::Path;
+
+// serialization/deserialization
+use serde::Serialize;
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::lock::LockFile;
+use crate::manifest::ManifestFile;
+use crate::{SyncError, JsonSnafu, NotLockedSnafu};
+
+// one package in the tree (or the manifest group that asks for it, at the leaves of an inverted tree)
+#[derive(Serialize, Debug)]
+pub struct Node {
+	pub name: String,
+	// the manifest group, for the wares.toml leaves of an inverted tree
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub group: Option<String>,
+	// what the package is locked to (a version, commit, branch, archive or path), None if it isn't in wares.lock
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub locked: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub url: Option<String>,
+	// where the package is installed
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+	// the package was already shown earlier in the tree, so its children aren't repeated
+	#[serde(skip_serializing_if = "std::ops::Not::not")]
+	pub duplicate: bool,
+	// the dependencies of the package (or the packages depending on it, in an inverted tree)
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub children: Vec<Node>
+}
+
+pub struct Tree<'a> {
+	manifest: &'a ManifestFile,
+	lockfile: &'a LockFile,
+	cache_folder: &'a Path,
+	// how many levels below the groups are shown, everything if None
+	depth: Option<usize>
+}
+
+impl<'a> Tree<'a> {
+	pub fn new(manifest: &'a ManifestFile, lockfile: &'a LockFile, cache_folder: &'a Path, depth: Option<usize>) -> Tree<'a> {
+		Tree { manifest, lockfile, cache_folder, depth }
+	}
+
+	fn leaf(&self, name: &str) -> Node {
+		let locked = self.lockfile.dependencies.get(name);
+		Node {
+			name: name.to_string(),
+			group: None,
+			locked: locked.map(|locked| locked.to_string()),
+			url: locked.map(|locked| locked.url().to_string()),
+			path: locked.map(|locked| locked.install_folder(self.cache_folder).to_string_lossy().to_string()),
+			duplicate: false,
+			children: Vec::new()
+		}
+	}
+
+	fn dependencies(&self, name: &str, level: usize, seen: &mut BTreeSet<String>) -> Node {
+		let mut node = self.leaf(name);
+		if !seen.insert(name.to_string()) {
+			node.duplicate = true;
+			return node;
+		}
+
+		if self.depth.is_none_or(|depth| level < depth) {
+			for child in self.lockfile.dependencies.get(name).map(|locked| locked.dependencies.as_slice()).unwrap_or_default() {
+				node.children.push(self.dependencies(child, level + 1, seen));
+			}
+		}
+		node
+	}
+
+	// the direct dependencies of a group and everything below them, packages that appear twice are only expanded once
+	pub fn group(&self, group: &str) -> Vec<Node> {
+		let mut seen = BTreeSet::new();
+		self.manifest.dependencies.get(group).into_iter().flatten()
+			.map(|dependency| self.dependencies(&dependency.name, 1, &mut seen))
+			.collect()
+	}
+
+	fn dependents(&self, name: &str, level: usize, seen: &mut BTreeSet<String>) -> Node {
+		let mut node = self.leaf(name);
+		if !seen.insert(name.to_string()) {
+			node.duplicate = true;
+			return node;
+		}
+
+		if self.depth.is_none_or(|depth| level < depth) {
+			for (group, dependencies) in &self.manifest.dependencies {
+				if dependencies.iter().any(|dependency| dependency.name == name) {
+					node.children.push(Node { name: String::from("wares.toml"), group: Some(group.clone()), locked: None, url: None, path: None, duplicate: false, children: Vec::new() });
+				}
+			}
+
+			for (parent, locked) in &self.lockfile.dependencies {
+				if locked.dependencies.iter().any(|dependency| dependency == name) {
+					node.children.push(self.dependents(parent, level + 1, seen));
+				}
+			}
+		}
+		node
+	}
+
+	// the packages that depend on a package, up to the manifest groups that ask for them (why is it in the build?)
+	pub fn invert(&self, name: &str) -> Result<Node, SyncError> {
+		let in_manifest = self.manifest.dependencies.values().flatten().any(|dependency| dependency.name == name);
+		if !in_manifest && !self.lockfile.dependencies.contains_key(name) {
+			return NotLockedSnafu{ name }.fail();
+		}
+
+		Ok(self.dependents(name, 0, &mut BTreeSet::new()))
+	}
+}
+
+// one line of the text tree: name, what it's locked to and where it's installed
+fn describe(node: &Node) -> String {
+	if let Some(group) = &node.group {
+		return format!("{} [{group}]", node.name);
+	}
+
+	let mut line = node.name.clone();
+	match (&node.locked, &node.path) {
+		(Some(locked), Some(path)) if Path::new(path).exists() => line += &format!(" {locked} ({path})"),
+		(Some(locked), _) => line += &format!(" {locked} (not installed)"),
+		(None, _) => line += " (not locked)"
+	}
+	if node.duplicate {
+		line += " (*)";
+	}
+	line
+}
+
+// draws nodes below a heading with box drawing characters (like `tree` or `cargo tree`)
+pub fn render_text(heading: &str, nodes: &[Node]) -> String {
+	fn render(nodes: &[Node], prefix: &str, output: &mut String) {
+		for (i, node) in nodes.iter().enumerate() {
+			let last = i + 1 == nodes.len();
+			*output += &format!("{prefix}{}{}\n", if last { "└── " } else { "├── " }, describe(node));
+			render(&node.children, &format!("{prefix}{}", if last { "    " } else { "│   " }), output);
+		}
+	}
+
+	let mut output = format!("{heading}\n");
+	render(nodes, "", &mut output);
+	output
+}
+
+// the one line heading of an inverted tree
+pub fn heading(node: &Node) -> String {
+	describe(node)
+}
+
+// the nodes as pretty printed json
+pub fn render_json<T: Serialize>(nodes: &T) -> Result<String, SyncError> {
+	serde_json::to_string_pretty(nodes).context(JsonSnafu)
+}
+
+// a graphviz graph with an edge from every node to each of its children
+pub fn render_dot(roots: &[(String, Vec<Node>)]) -> String {
+	// labels keep their \n escapes, which graphviz turns into line breaks
+	fn quote(text: &str) -> String {
+		format!("\"{}\"", text.replace('"', "\\\""))
+	}
+
+	fn id(node: &Node) -> String {
+		match &node.group {
+			Some(group) => format!("[{group}]"),
+			None => node.name.clone()
+		}
+	}
+
+	fn walk(parent: &str, nodes: &[Node], lines: &mut Vec<String>) {
+		for node in nodes {
+			let label = match (&node.group, &node.locked) {
+				(Some(_), _) => format!("\t{} [shape=box];", quote(&id(node))),
+				(None, Some(locked)) => format!("\t{} [label={}];", quote(&id(node)), quote(&format!("{}\\n{locked}", node.name))),
+				(None, None) => format!("\t{};", quote(&id(node)))
+			};
+			let edge = format!("\t{} -> {};", quote(parent), quote(&id(node)));
+
+			for line in [label, edge] {
+				if !lines.contains(&line) {
+					lines.push(line);
+				}
+			}
+			walk(&id(node), &node.children, lines);
+		}
+	}
+
+	let mut lines = Vec::new();
+	for (root, nodes) in roots {
+		lines.push(format!("\t{} [shape=box];", quote(root)));
+		walk(root, nodes, &mut lines);
+	}
+
+	format!("digraph wares {{\n{}\n}}\n", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::{LockedDependency, LockedDependencyId};
+
+    fn project() -> (ManifestFile, LockFile) {
+        let manifest = ManifestFile::parse("manifest_version = 1\n[dependencies]\nengine = \"gh:a/engine\"\nfmt = \"gh:fmtlib/fmt\"\n[dev-dependencies]\ndoctest = \"gh:doctest/doctest\"\n").unwrap();
+
+        let mut lockfile = LockFile::new();
+        for (name, dependencies) in [("engine", vec!["fmt", "glm"]), ("glm", vec!["fmt"]), ("fmt", vec![])] {
+            let mut locked = LockedDependency::new(format!("https://github.com/a/{name}.git"), LockedDependencyId::MainBranch);
+            locked.dependencies = dependencies.iter().map(|dependency| dependency.to_string()).collect();
+            lockfile.dependencies.insert(name.to_string(), locked);
+        }
+        (manifest, lockfile)
+    }
+
+    #[test]
+    fn check_tree() {
+        let (manifest, lockfile) = project();
+        let tree = Tree::new(&manifest, &lockfile, Path::new("/nonexistent"), None);
+
+        let nodes = tree.group("dependencies");
+        assert_eq!(render_text("[dependencies]", &nodes), "[dependencies]\n\
+            ├── engine main branch (not installed)\n\
+            │   ├── fmt main branch (not installed)\n\
+            │   └── glm main branch (not installed)\n\
+            │       └── fmt main branch (not installed) (*)\n\
+            └── fmt main branch (not installed) (*)\n");
+
+        let shallow = Tree::new(&manifest, &lockfile, Path::new("/nonexistent"), Some(1));
+        assert!(shallow.group("dependencies").iter().all(|node| node.children.is_empty()));
+        assert_eq!(render_text("[dev-dependencies]", &shallow.group("dev-dependencies")), "[dev-dependencies]\n└── doctest (not locked)\n");
+    }
+
+    #[test]
+    fn check_invert() {
+        let (manifest, lockfile) = project();
+        let tree = Tree::new(&manifest, &lockfile, Path::new("/nonexistent"), None);
+
+        let node = tree.invert("fmt").unwrap();
+        assert_eq!(render_text(&heading(&node), &node.children), "fmt main branch (not installed)\n\
+            ├── wares.toml [dependencies]\n\
+            ├── engine main branch (not installed)\n\
+            │   └── wares.toml [dependencies]\n\
+            └── glm main branch (not installed)\n    \
+                └── engine main branch (not installed) (*)\n");
+
+        assert!(tree.invert("zlib").is_err());
+
+        let dot = render_dot(&[(String::from("[dependencies]"), tree.group("dependencies"))]);
+        assert!(dot.contains("\t\"[dependencies]\" -> \"engine\";\n\t\"fmt\" [label=\"fmt\\nmain branch\"];\n\t\"engine\" -> \"fmt\";"), "{dot}");
+    }
+}
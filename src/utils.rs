@@ -1,6 +1,9 @@
-use std::path::PathBuf;
-use std::env;
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, thread};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::num::ParseIntError;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // parses a hex value in a string, returning an array of u8 of a certain size
 // if the templated argument N is smaller than value, the end will be truncated
@@ -16,6 +19,11 @@ pub fn parse_hex<const N: usize>(value: &str) -> Result<[u8; N], ParseIntError>
 	Ok(result)
 }
 
+// true if value is a sha256 hash written out in hex (64 hexadecimal digits)
+pub fn is_sha256(value: &str) -> bool {
+	value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
 // constant variable for format_hex
 const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
 
@@ -53,6 +61,132 @@ pub fn cache_dir_fallback() -> PathBuf {
 	PathBuf::from(env::var("WARES_CACHE").unwrap_or(String::from("./.wares_cache")))
 }
 
+// provide a fallback for the user's config file (WARES_CONFIG environment variable, or wares/config.toml in the user's config folder)
+pub fn config_file_fallback() -> Option<PathBuf> {
+	if let Ok(file) = env::var("WARES_CONFIG") {
+		return Some(PathBuf::from(file));
+	}
+
+	let config_dir = if cfg!(windows) {
+		env::var("APPDATA").ok().map(PathBuf::from)
+	} else {
+		env::var("XDG_CONFIG_HOME").ok().filter(|dir| !dir.is_empty()).map(PathBuf::from)
+			.or_else(|| env::var("HOME").ok().map(|home| PathBuf::from(home).join(".config")))
+	};
+
+	config_dir.map(|dir| dir.join("wares").join("config.toml"))
+}
+
+// provide a fallback for offline mode (WARES_OFFLINE environment variable)
+pub fn offline_fallback() -> bool {
+	env::var("WARES_OFFLINE").is_ok_and(|value| !matches!(value.to_lowercase().as_str(), "" | "0" | "false" | "no" | "off"))
+}
+
+// provide a fallback for the number of parallel jobs (WARES_JOBS environment variable, or the number of cpus)
+pub fn jobs_fallback() -> usize {
+	env::var("WARES_JOBS").ok()
+		.and_then(|value| value.parse::<usize>().ok())
+		.filter(|jobs| *jobs > 0)
+		.unwrap_or_else(|| thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1))
+}
+
+// calls work on every item using up to jobs threads, returning the results in the order of the items
+pub fn parallel_map<T: Sync, R: Send, F: Fn(&T) -> R + Sync>(items: &[T], jobs: usize, work: F) -> Vec<R> {
+	let next = AtomicUsize::new(0);
+	let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+	thread::scope(|scope| {
+		for _ in 0..jobs.clamp(1, items.len().max(1)) {
+			scope.spawn(|| loop {
+				let index = next.fetch_add(1, Ordering::SeqCst);
+				let Some(item) = items.get(index) else {
+					break;
+				};
+				let result = work(item);
+				results.lock().unwrap()[index] = Some(result);
+			});
+		}
+	});
+
+	results.into_inner().unwrap().into_iter().map(|result| result.expect("every item is worked on")).collect()
+}
+
+// seconds since the unix epoch
+pub fn unix_time() -> u64 {
+	unix_time_of(SystemTime::now())
+}
+
+pub fn unix_time_of(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
+// parses a duration like "30d" or "12h" (s, m, h, d or w) into seconds
+pub fn parse_duration(value: &str) -> Option<u64> {
+	let value = value.trim();
+	let unit_start = value.find(|c: char| !c.is_ascii_digit())?;
+	let amount: u64 = value[..unit_start].parse().ok()?;
+
+	let unit = match &value[unit_start..] {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 60 * 60 * 24,
+		"w" => 60 * 60 * 24 * 7,
+		_ => return None
+	};
+
+	amount.checked_mul(unit)
+}
+
+// the total size of the files in a folder, in bytes
+pub fn dir_size(path: &Path) -> u64 {
+	fs::read_dir(path).into_iter().flatten().flatten().map(|entry| {
+		match entry.file_type() {
+			Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+			Ok(_) => entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+			Err(_) => 0
+		}
+	}).sum()
+}
+
+// formats a size in bytes for humans
+pub fn format_size(bytes: u64) -> String {
+	const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+	let mut size = bytes as f64;
+	let mut unit = 0;
+	while size >= 1024.0 && unit < UNITS.len() - 1 {
+		size /= 1024.0;
+		unit += 1;
+	}
+
+	if unit == 0 {
+		format!("{bytes} {}", UNITS[0])
+	} else {
+		format!("{size:.1} {}", UNITS[unit])
+	}
+}
+
+// removes a folder and everything in it
+// git marks its object files as read-only, which stops windows from deleting them
+pub fn remove_dir_all(path: &Path) -> io::Result<()> {
+	if cfg!(windows) {
+		for entry in fs::read_dir(path)?.flatten() {
+			let entry_path = entry.path();
+			if entry.file_type()?.is_dir() {
+				remove_dir_all(&entry_path)?;
+			} else {
+				let mut permissions = entry.metadata()?.permissions();
+				#[allow(clippy::permissions_set_readonly_false)]
+				permissions.set_readonly(false);
+				fs::set_permissions(&entry_path, permissions)?;
+			}
+		}
+	}
+
+	fs::remove_dir_all(path)
+}
+
 // convert a relative path to an absolute path
 pub fn get_full_path<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result<PathBuf, std::io::Error> {
 	let full_path = std::fs::canonicalize(path)?;
@@ -63,6 +197,37 @@ pub fn get_full_path<P: std::convert::AsRef<std::path::Path>>(path: P) -> Result
 	}) // consider using dunce crate instead
 }
 
+// the path that leads from base to path, both paths have to be absolute
+// returns None if there isn't one (i.e. they're on different drives)
+pub fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+	let mut path_components = path.components().peekable();
+	let mut base_components = base.components().peekable();
+
+	// skip the part both paths share
+	while let (Some(a), Some(b)) = (path_components.peek(), base_components.peek()) {
+		if a != b {
+			break;
+		}
+		path_components.next();
+		base_components.next();
+	}
+
+	let mut relative = PathBuf::new();
+	for component in base_components {
+		match component {
+			std::path::Component::Normal(_) => relative.push(".."),
+			std::path::Component::CurDir => {},
+			_ => return None
+		}
+	}
+	relative.extend(path_components);
+
+	if relative.as_os_str().is_empty() {
+		relative.push(".");
+	}
+	Some(relative)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -80,6 +245,35 @@ mod tests {
     	assert_eq!(result, "5900f1");
     }
 
+    #[test]
+    fn check_parse_duration() {
+        assert_eq!(parse_duration("30d"), Some(30 * 24 * 60 * 60));
+        assert_eq!(parse_duration("90m"), Some(90 * 60));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("3y"), None);
+    }
+
+    #[test]
+    fn check_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(3 * 1024 * 1024 + 512 * 1024), "3.5 MiB");
+    }
+
+    #[test]
+    fn check_parallel_map() {
+        let items: Vec<u32> = (0..100).collect();
+        assert_eq!(parallel_map(&items, 8, |item| item * 2), items.iter().map(|item| item * 2).collect::<Vec<u32>>());
+        assert_eq!(parallel_map(&Vec::<u32>::new(), 8, |item| *item), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn check_relative_path() {
+        assert_eq!(relative_path(Path::new("/home/me/libs/mycore"), Path::new("/home/me/game")), Some(PathBuf::from("../libs/mycore")));
+        assert_eq!(relative_path(Path::new("/home/me/game/vendor/glfw"), Path::new("/home/me/game")), Some(PathBuf::from("vendor/glfw")));
+        assert_eq!(relative_path(Path::new("/home/me/game"), Path::new("/home/me/game")), Some(PathBuf::from(".")));
+    }
+
     #[test]
     fn check_filename_sanitization() {
         let result = sanitize_filename("test/file\\\\*sanitized?123<>\"|:");
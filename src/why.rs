@@ -0,0 +1,237 @@
+// where a package comes from (wares why)
+//
+// every chain of dependencies from the groups of wares.toml down to the package is listed with the requirement
+// placed at each link. the requirements of a package are read from its own wares.toml (the same way the resolver
+// reads them), so packages that aren't installed yet only show their dependency names from wares.lock.
+
+// standard libraries
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::fmt;
+
+// error handling
+use snafu::ResultExt;
+
+// internal dependencies
+use crate::lock::{LockFile, LockedDependency};
+use crate::manifest::{ManifestFile, Specifier};
+use crate::{utils, SyncError, IoSnafu, NotLockedSnafu};
+
+// one package in a chain and the requirement its parent placed on it (None if it isn't known)
+#[derive(Clone, Debug)]
+pub struct Link {
+	pub package: String,
+	pub requirement: Option<Specifier>,
+	pub locked: Option<String>
+}
+
+// a chain of dependencies from a group of wares.toml to the package
+#[derive(Clone, Debug)]
+pub struct Chain {
+	pub group: String,
+	pub links: Vec<Link>
+}
+
+impl fmt::Display for Chain {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "wares.toml [{}]", self.group)?;
+		for (i, link) in self.links.iter().enumerate() {
+			write!(f, " -> {}", link.package)?;
+			match &link.requirement {
+				Some(requirement) => write!(f, " {requirement}")?,
+				None => write!(f, " (requirement unknown, it isn't installed)")?
+			}
+			// the package itself is described by its origin
+			if let (Some(locked), false) = (&link.locked, i + 1 == self.links.len()) {
+				write!(f, " ({locked})")?;
+			}
+		}
+		Ok(())
+	}
+}
+
+// where the package that's used comes from
+#[derive(Debug)]
+pub enum Origin {
+	// the folder was given with --override:<name>=<folder>
+	Override(String),
+	// wares.lock locks it and every requirement still allows it, so syncing keeps it
+	Locked(LockedDependency),
+	// a sync has to resolve it (again): it isn't locked, or the requirements listed don't allow what's locked
+	Unlocked{ locked: Option<LockedDependency>, rejected_by: Vec<String> }
+}
+
+#[derive(Debug)]
+pub struct Explanation {
+	pub package: String,
+	pub chains: Vec<Chain>,
+	pub origin: Origin
+}
+
+// the commit and ref a locked dependency was resolved to, i.e. " (commit 1a2b3c4 from refs/tags/v1.2.0)"
+fn source_of(locked: &LockedDependency) -> String {
+	match (locked.oid(), &locked.reference) {
+		(Some(oid), Some(reference)) => format!(" (commit {:.7} from {reference})", oid.to_string()),
+		(Some(oid), None) if locked.version.is_some() => format!(" (commit {:.7})", oid.to_string()),
+		(None, Some(reference)) => format!(" (from {reference})"),
+		_ => String::new()
+	}
+}
+
+impl fmt::Display for Explanation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{} is required by:", self.package)?;
+		for chain in &self.chains {
+			writeln!(f, "    {chain}")?;
+		}
+		if self.chains.is_empty() {
+			writeln!(f, "    nothing in wares.toml (a sync would remove it from wares.lock)")?;
+		}
+
+		match &self.origin {
+			Origin::Override(folder) => write!(f, "{} is used from {folder}, which was given with --override:{}", self.package, self.package),
+			Origin::Locked(locked) => write!(f, "{} {locked}{} comes from wares.lock, every requirement allows it so syncing keeps it (`wares update {}` resolves it again)",
+				self.package, source_of(locked), self.package),
+			Origin::Unlocked{ locked: None, .. } => write!(f, "{} isn't in wares.lock, the next sync resolves it to the newest candidate every requirement allows", self.package),
+			Origin::Unlocked{ locked: Some(locked), rejected_by } => {
+				write!(f, "wares.lock locks {} to {locked}{}, which {} doesn't allow, so the next sync resolves it again",
+					self.package, source_of(locked), rejected_by.join(" and "))
+			}
+		}
+	}
+}
+
+pub struct Why<'a> {
+	manifest: &'a ManifestFile,
+	lockfile: &'a LockFile,
+	cache_folder: &'a Path,
+	overrides: &'a BTreeMap<String, String>
+}
+
+impl<'a> Why<'a> {
+	pub fn new(manifest: &'a ManifestFile, lockfile: &'a LockFile, cache_folder: &'a Path, overrides: &'a BTreeMap<String, String>) -> Why<'a> {
+		Why { manifest, lockfile, cache_folder, overrides }
+	}
+
+	fn link(&self, package: &str, requirement: Option<Specifier>) -> Link {
+		Link { package: package.to_string(), requirement, locked: self.lockfile.dependencies.get(package).map(|locked| locked.to_string()) }
+	}
+
+	// the dependencies of a package, from its wares.toml if it's installed (or overridden), otherwise from wares.lock
+	fn dependencies(&self, package: &str) -> Result<Vec<Link>, SyncError> {
+		let locked = self.lockfile.dependencies.get(package);
+		let folder: Option<PathBuf> = match (self.overrides.get(package), locked) {
+			(Some(folder), _) => Some(utils::get_full_path(folder).context(IoSnafu{ context: format!("grabbing full path of {folder}") })?),
+			(None, Some(locked)) => Some(locked.install_folder(self.cache_folder)).filter(|folder| folder.exists()),
+			(None, None) => None
+		};
+
+		if let Some(manifest) = folder.map(|folder| crate::read_nested_manifest(&folder)).transpose()?.flatten() {
+			// only the default group is transitive
+			return Ok(manifest.dependencies.get("dependencies").into_iter().flatten()
+				.map(|dependency| self.link(&dependency.name, Some(dependency.specifier().clone())))
+				.collect());
+		}
+
+		Ok(locked.map(|locked| locked.dependencies.as_slice()).unwrap_or_default().iter()
+			.map(|dependency| self.link(dependency, None))
+			.collect())
+	}
+
+	fn search(&self, package: &str, chain: &mut Chain, chains: &mut Vec<Chain>) -> Result<(), SyncError> {
+		let last = chain.links.last().map(|link| link.package.clone()).unwrap_or_default();
+		if last == package {
+			chains.push(chain.clone());
+			return Ok(());
+		}
+
+		for link in self.dependencies(&last)? {
+			// dependency cycles are only followed once
+			if chain.links.iter().any(|previous| previous.package == link.package) {
+				continue;
+			}
+			chain.links.push(link);
+			self.search(package, chain, chains)?;
+			chain.links.pop();
+		}
+		Ok(())
+	}
+
+	// every chain from wares.toml to the package, and where the package that's used comes from
+	pub fn explain(&self, package: &str) -> Result<Explanation, SyncError> {
+		let mut chains = Vec::new();
+		for (group, dependencies) in &self.manifest.dependencies {
+			for dependency in dependencies {
+				let mut chain = Chain { group: group.clone(), links: vec![self.link(&dependency.name, Some(dependency.specifier().clone()))] };
+				self.search(package, &mut chain, &mut chains)?;
+			}
+		}
+
+		let locked = self.lockfile.dependencies.get(package);
+		if chains.is_empty() && locked.is_none() && !self.overrides.contains_key(package) {
+			return NotLockedSnafu{ name: package }.fail();
+		}
+
+		// only version requirements can be checked without asking the repository
+		let mut rejected_by: Vec<String> = chains.iter()
+			.filter_map(|chain| chain.links.last().and_then(|link| link.requirement.clone()))
+			.filter(|requirement| match (requirement, locked.and_then(|locked| locked.version.as_ref())) {
+				(Specifier::Version(requirement), Some(version)) => !requirement.matches(version),
+				(Specifier::Version(_), None) => locked.is_some(),
+				_ => false
+			})
+			.map(|requirement| requirement.to_string())
+			.collect();
+		rejected_by.sort();
+		rejected_by.dedup();
+
+		let origin = match (self.overrides.get(package), locked) {
+			(Some(folder), _) => Origin::Override(folder.clone()),
+			(None, Some(locked)) if rejected_by.is_empty() => Origin::Locked(locked.clone()),
+			(None, locked) => Origin::Unlocked{ locked: locked.cloned(), rejected_by }
+		};
+
+		Ok(Explanation { package: package.to_string(), chains, origin })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lock::LockedDependencyId;
+    use semver::Version;
+
+    #[test]
+    fn check_explain() {
+        let manifest = ManifestFile::parse("manifest_version = 1\n[dependencies]\nengine = \"gh:a/engine@^1\"\nfmt = \"gh:fmtlib/fmt@^9\"\n[dev-dependencies]\nbench = \"gh:a/bench\"\n").unwrap();
+
+        let mut lockfile = LockFile::new();
+        for (name, version, dependencies) in [("engine", "1.2.0", vec!["fmt"]), ("fmt", "9.1.0", vec![]), ("bench", "0.1.0", vec!["engine"])] {
+            let mut locked = LockedDependency::new(format!("https://github.com/a/{name}.git"), LockedDependencyId::Oid(git2::Oid::zero()));
+            locked.version = Some(Version::parse(version).unwrap());
+            locked.reference = Some(format!("refs/tags/v{version}"));
+            locked.dependencies = dependencies.iter().map(|dependency| dependency.to_string()).collect();
+            lockfile.dependencies.insert(name.to_string(), locked);
+        }
+
+        let overrides = BTreeMap::new();
+        let why = Why::new(&manifest, &lockfile, Path::new("/nonexistent"), &overrides);
+
+        let explanation = why.explain("fmt").unwrap();
+        assert_eq!(explanation.to_string(), "fmt is required by:\n    \
+            wares.toml [dependencies] -> engine ^1 (1.2.0) -> fmt (requirement unknown, it isn't installed)\n    \
+            wares.toml [dependencies] -> fmt ^9\n    \
+            wares.toml [dev-dependencies] -> bench main branch (0.1.0) -> engine (requirement unknown, it isn't installed) (1.2.0) -> fmt (requirement unknown, it isn't installed)\n\
+            fmt 9.1.0 (commit 0000000 from refs/tags/v9.1.0) comes from wares.lock, every requirement allows it so syncing keeps it (`wares update fmt` resolves it again)");
+
+        lockfile.dependencies.get_mut("fmt").unwrap().version = Some(Version::new(8, 0, 0));
+        let why = Why::new(&manifest, &lockfile, Path::new("/nonexistent"), &overrides);
+        assert!(matches!(why.explain("fmt").unwrap().origin, Origin::Unlocked{ locked: Some(_), ref rejected_by } if rejected_by.len() == 1));
+
+        let overrides = BTreeMap::from([(String::from("fmt"), String::from("/src/fmt"))]);
+        let why = Why::new(&manifest, &lockfile, Path::new("/nonexistent"), &overrides);
+        assert!(matches!(why.explain("fmt").unwrap().origin, Origin::Override(_)));
+
+        assert!(why.explain("zlib").is_err());
+    }
+}